@@ -5,15 +5,19 @@ import matplotlib.pyplot as plt
 import matplotlib.animation as animation
 from matplotlib.gridspec import GridSpec
 
-from read_frames import read_frames
+import sys
 
-with open("constants.json") as f:
+from read_frames import read_frames, run_dir
+
+# a run directory, by default the latest run
+RUN_DIR = run_dir(sys.argv)
+with open(RUN_DIR / "constants.json") as f:
     constants = json.load(f)
 
 WORLD_W, WORLD_H = constants["world_shape"]
 SEED = constants["seed"]
-FRAMES_PATH = f"runs/frames/{SEED}.bin"
-STATS_PATH = f"runs/seed{SEED}.csv"
+FRAMES_PATH = RUN_DIR / "frames.bin"
+STATS_PATH = RUN_DIR / "stats.csv"
 
 # ---- load the full stats log once; we reveal it progressively as the animation plays ----
 ages, prey_counts, pred_counts = [], [], []
@@ -37,7 +41,7 @@ ax_world.set_ylim(0, WORLD_H)
 ax_world.set_aspect("equal")
 
 # scatter's `s` is an area in points^2, a screen-space unit with no inherent relationship
-# to data coordinates. To make plotted circle size == sqrt(energy) (the exact radius Rust's
+# to data coordinates. To make plotted circle size == sqrt(body_mass + energy) (the exact radius Rust's
 # Blob::radius() computes, and the exact value check_pred_interactions sums to test
 # collisions), convert data-unit radius -> points using the axes' own transform. This only
 # stays valid as long as xlim/ylim/aspect/figure-size don't change after this point, which
@@ -71,10 +75,10 @@ def update(indexed_frame):
     pred = blobs[blobs["type"] == 1]
 
     prey_scatter.set_offsets(np.column_stack([prey["x"], prey["y"]]))
-    prey_scatter.set_sizes(radius_to_size(np.sqrt(prey["energy"])))
+    prey_scatter.set_sizes(radius_to_size(np.sqrt(prey["body_mass"] + prey["energy"])))
 
     pred_scatter.set_offsets(np.column_stack([pred["x"], pred["y"]]))
-    pred_scatter.set_sizes(radius_to_size(np.sqrt(pred["energy"])))
+    pred_scatter.set_sizes(radius_to_size(np.sqrt(pred["body_mass"] + pred["energy"])))
 
     title.set_text(f"age {age}   prey {len(prey)}   predators {len(pred)}")
 
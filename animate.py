@@ -3,13 +3,17 @@ import numpy as np
 import matplotlib.pyplot as plt
 import matplotlib.animation as animation
 
-from read_frames import read_frames
+import sys
 
-with open("constants.json") as f:
+from read_frames import read_frames, run_dir
+
+# a run directory, by default the latest run
+RUN_DIR = run_dir(sys.argv)
+with open(RUN_DIR / "constants.json") as f:
     constants = json.load(f)
 
 WORLD_W, WORLD_H = constants["world_shape"]
-FRAMES_PATH = f"runs/frames/{constants['seed']}.bin"
+FRAMES_PATH = RUN_DIR / "frames.bin"
 
 
 fig, ax = plt.subplots(figsize=(10, 6))
@@ -37,10 +41,10 @@ def update(frame):
     pred = blobs[blobs["type"] == 1]
 
     prey_scatter.set_offsets(np.column_stack([prey["x"], prey["y"]]))
-    prey_scatter.set_sizes(radius_to_size(np.sqrt(prey["energy"])))  # area ~ energy, since radius ~ sqrt(energy)
+    prey_scatter.set_sizes(radius_to_size(np.sqrt(prey["body_mass"] + prey["energy"])))  # area ~ mass + energy, since radius ~ sqrt of it
 
     pred_scatter.set_offsets(np.column_stack([pred["x"], pred["y"]]))
-    pred_scatter.set_sizes(radius_to_size(np.sqrt(pred["energy"])))
+    pred_scatter.set_sizes(radius_to_size(np.sqrt(pred["body_mass"] + pred["energy"])))
 
     title.set_text(f"age {age}   prey {len(prey)}   predators {len(pred)}")
 
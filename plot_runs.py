@@ -1,9 +1,11 @@
-import argparse
+import sys
 from pathlib import Path
 
 import pandas as pd
 import matplotlib.pyplot as plt
 
+from read_frames import run_dirs
+
 
 PREY_COLOR = "green"
 PRED_COLOR = "red"
@@ -156,20 +158,23 @@ def plot_all(runs, title, output_path=None, plot_energy=False):
 
 
 def main():
-    directory = Path("./")
-
-    csv_files = sorted(directory.glob("*.csv"))
+    # the run directories given, or every run under runs/
+    directories = run_dirs(sys.argv)
 
-    if not csv_files:
-        raise SystemExit(f"No CSV files found in {directory}")
+    if not directories:
+        raise SystemExit("No runs found")
 
-    output_dir = directory / "runs/plots"
-    output_dir.mkdir(exist_ok=True)
+    output_dir = Path("runs/plots")
+    output_dir.mkdir(parents=True, exist_ok=True)
 
     runs = []
 
 
-    for csv_file in csv_files:
+    for directory in directories:
+        csv_file = directory / "stats.csv"
+        if not csv_file.exists():
+            print(f"Skipping {directory}: no stats.csv")
+            continue
         df = pd.read_csv(csv_file)
 
         required_columns = {
@@ -196,22 +201,22 @@ def main():
             df["mean_pred_energy"] * df["predators"]
         )
 
-        runs.append((csv_file.stem, df))
+        runs.append((directory.name, df))
 
         # One plot per seed/run
 
         if plot_each_run:
-            output_path = output_dir / f"{csv_file.stem}.png"
+            output_path = output_dir / f"{directory.name}.png"
 
             plot_run(
                 df,
-                title=f"Population {'and Total energy' if plot_energy else ''} vs Age per species ({csv_file.stem})",
+                title=f"Population {'and Total energy' if plot_energy else ''} vs Age per species ({directory.name})",
                 output_path= output_path,
                 plot_energy=plot_energy
             )
 
     if not runs:
-        raise SystemExit("No valid CSV files found.")
+        raise SystemExit("No runs with the population and energy columns found.")
 
     # Combined plot
     combined_path = output_dir / "all_runs.png"
@@ -9,6 +9,19 @@ File layout (repeated until EOF), written little-endian:
         y       : f32 (4 bytes)
         type    : u8  (1 byte)   0 = Prey, 1 = Predator
         energy  : f32 (4 bytes)
+        body_mass, metabolism, top_speed, turn_rate : f32 each (16 bytes)
+        id          : u64 (8 bytes)  unique, founders are 1..N
+        parent      : u64 (8 bytes)  0 for founders
+        birth_age   : i32 (4 bytes)
+        generation  : u32 (4 bytes)
+    }
+    field_count : u8  (1 byte)   0, or 2 when pheromones are on (prey, predator)
+    field_count * {
+        cols    : u32 (4 bytes)
+        rows    : u32 (4 bytes)
+        cell_w  : f32 (4 bytes)
+        cell_h  : f32 (4 bytes)
+        rows * cols * f32        row-major, row 0 at y = 0
     }
 
 Each frame can have a DIFFERENT blob_count (births/deaths change the
@@ -17,20 +30,36 @@ np.fromfile() the whole thing with one dtype. Parse it frame by frame:
 read the small header to find out how many blob records follow, then
 let numpy read just that slice in one shot.
 """
+import json
 import struct
+from pathlib import Path
+
 import numpy as np
 
 HEADER = struct.Struct('<QI')          # age (u64) + blob_count (u32) = 12 bytes
+FIELD_HEADER = struct.Struct('<IIff')   # cols, rows, cell_w, cell_h = 16 bytes
 BLOB_DTYPE = np.dtype([
     ('x', '<f4'),
     ('y', '<f4'),
     ('type', 'u1'),                    # 0 = Prey, 1 = Predator
     ('energy', '<f4'),
-])                                      # 13 bytes/blob, tightly packed (verified, no padding)
+    ('body_mass', '<f4'),
+    ('metabolism', '<f4'),
+    ('top_speed', '<f4'),
+    ('turn_rate', '<f4'),
+    ('id', '<u8'),
+    ('parent', '<u8'),                 # 0 = founder
+    ('birth_age', '<i4'),
+    ('generation', '<u4'),
+])                                      # 53 bytes/blob, tightly packed (no padding)
 
 
-def read_frames(path):
-    """Yield (age, structured_array_of_blobs) for every frame in the file."""
+def read_frames(path, with_fields=False):
+    """Yield (age, structured_array_of_blobs) for every frame in the file.
+
+    With with_fields=True, yield (age, blobs, fields) where fields is a list
+    of 2D (rows, cols) pheromone grids, empty when pheromones were off.
+    """
     with open(path, 'rb') as f:
         data = f.read()
 
@@ -44,12 +73,42 @@ def read_frames(path):
         blobs = np.frombuffer(data, dtype=BLOB_DTYPE, count=count, offset=offset)
         offset += nbytes
 
-        yield age, blobs
+        field_count = data[offset]
+        offset += 1
+        fields = []
+        for _ in range(field_count):
+            cols, rows, _cell_w, _cell_h = FIELD_HEADER.unpack_from(data, offset)
+            offset += FIELD_HEADER.size
+            grid = np.frombuffer(data, dtype='<f4', count=rows * cols, offset=offset)
+            fields.append(grid.reshape(rows, cols))
+            offset += rows * cols * 4
+
+        if with_fields:
+            yield age, blobs, fields
+        else:
+            yield age, blobs
+
+
+def run_dir(argv, root="runs"):
+    """The run directory given on the command line, or the one started last under root."""
+    if len(argv) > 1:
+        return Path(argv[1])
+    runs = [d for d in Path(root).iterdir() if (d / "manifest.json").exists()]
+    if not runs:
+        raise SystemExit(f"No runs in {root}")
+    return max(runs, key=lambda d: json.loads((d / "manifest.json").read_text())["started"])
+
+
+def run_dirs(argv, root="runs"):
+    """The run directories given on the command line, or every run under root."""
+    if len(argv) > 1:
+        return [Path(arg) for arg in argv[1:]]
+    return sorted(d for d in Path(root).iterdir() if (d / "manifest.json").exists())
 
 
 if __name__ == "__main__":
     import sys
-    path = sys.argv[1] if len(sys.argv) > 1 else "./runs/frames/12.bin"
+    path = run_dir(sys.argv) / "frames.bin"
     for age, blobs in read_frames(path):
         prey = (blobs['type'] == 0).sum()
         pred = (blobs['type'] == 1).sum()
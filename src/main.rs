@@ -1,22 +1,312 @@
-mod mods;
-
 use clap::Parser;
-use mods::cli::Cli;
-use mods::world::World;
-use rand::rngs::StdRng;
+use evolution::mods::agents::{Controller, ProcessAgent, ReplayAgent};
+use evolution::mods::checkpoints::{open_output, Checkpoint, WorldRng};
+use evolution::mods::cli::{parse_seeds, Cli, Commands, OnLoad, RunArgs, SaveArgs};
+use evolution::mods::runs::RunDir;
+use evolution::mods::world::World;
 use rand::SeedableRng;
+use std::fs;
+use std::path::Path;
 
-use crate::mods::cli::parse_command;
-use crate::mods::constants::load_constants;
+use evolution::mods::constants::{Constants, LoadPolicy};
+use evolution::mods::errors::{Error, Result};
+use evolution::mods::stats::Stats;
+use evolution::mods::{hashes, inspect, render, saves};
 
 fn main() {
+    if let Err(error) = run(Cli::parse()) {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // only read by the commands that need constants, the others work from their files alone
+    let constants = || -> Result<Constants> {
+        let mut constants = Constants::from_file(&cli.config)?.with_overrides(&cli.overrides)?;
+        if let Some(seed) = cli.seed {
+            constants.seed = seed;
+        }
+        Ok(constants)
+    };
+    // --set and --seed are the overrides of a loaded world's constants, which
+    // keeping them would silently drop
+    let overridden = !cli.overrides.is_empty() || cli.seed.is_some();
+    let policy = |saves: &SaveArgs| match saves.on_load {
+        OnLoad::Keep if overridden && saves.load.is_some() => Err(Error::Config {
+            field: "on_load".to_string(),
+            message: "keep ignores --set and --seed, use override or replace".to_string(),
+        }),
+        OnLoad::Keep => Ok(LoadPolicy::Keep),
+        OnLoad::Replace => Ok(LoadPolicy::Replace),
+        OnLoad::Override => {
+            let mut overrides = cli.overrides.clone();
+            overrides.extend(cli.seed.map(|seed| format!("seed={seed}")));
+            Ok(LoadPolicy::Override(overrides))
+        }
+    };
+
+    match cli.command.unwrap_or_default() {
+        Commands::Run(RunArgs { saves, name, agent }) => {
+            let steering = match agent {
+                Some(command) => Steering::Agent(command),
+                None => Steering::Brains,
+            };
+            let policy = policy(&saves)?;
+            simulate(
+                constants()?,
+                saves.load,
+                saves.save,
+                &policy,
+                steering,
+                &cli.runs_dir,
+                name,
+            )?;
+            Ok(())
+        }
+        Commands::Resume { run, agent, replay } => {
+            let (run_dir, path) = match run {
+                Some(path) if Path::new(&path).is_dir() => {
+                    let run_dir = RunDir::open(&path)?;
+                    let checkpoint = Checkpoint::latest(&run_dir.checkpoints())?;
+                    (run_dir, checkpoint)
+                }
+                Some(path) => (RunDir::of_checkpoint(&path)?, path),
+                None => {
+                    let run_dir = RunDir::latest(&cli.runs_dir, constants()?.seed)?;
+                    let checkpoint = Checkpoint::latest(&run_dir.checkpoints())?;
+                    (run_dir, checkpoint)
+                }
+            };
+            let steering = match (agent, replay) {
+                (Some(command), _) => Steering::Agent(command),
+                (None, Some(log_path)) => Steering::Replay(log_path),
+                (None, None) => Steering::Brains,
+            };
+            println!("Hello, blobworld!");
+            println!("resuming from {path}");
+            let checkpoint = Checkpoint::load(&path)?;
+            run_dir.resumed()?;
+            let outcome =
+                controller(steering, &run_dir, Some(&checkpoint)).and_then(|mut controller| {
+                    World::resume(&run_dir, checkpoint, controller.as_deref_mut())
+                });
+            run_dir.finish(&outcome)?;
+            outcome.map(|_| ())
+        }
+        Commands::Replay {
+            agent_log,
+            saves,
+            name,
+        } => {
+            let policy = policy(&saves)?;
+            let steering = Steering::Replay(agent_log);
+            simulate(
+                constants()?,
+                saves.load,
+                saves.save,
+                &policy,
+                steering,
+                &cli.runs_dir,
+                name,
+            )?;
+            Ok(())
+        }
+        Commands::Verify { run } => verify(&run),
+        Commands::Render {
+            frames,
+            out_dir,
+            every,
+        } => {
+            // frames in a run directory are drawn with the constants the run had
+            let run_constants = Path::new(&frames).with_file_name("constants.json");
+            let constants = if run_constants.exists() {
+                let path = run_constants.to_string_lossy();
+                Constants::from_file(&path)?.with_overrides(&cli.overrides)?
+            } else {
+                constants()?
+            };
+            let written = render::render_frames(&frames, &out_dir, every, &constants)?;
+            println!("{written} frames drawn into {out_dir}");
+            Ok(())
+        }
+        Commands::Stats {
+            frames,
+            columns,
+            output,
+        } => Stats::write_frames_csv(&frames, &columns, output.as_deref()),
+        Commands::Inspect { save } => {
+            let world = World::load_from_file(&save)?;
+            print!("{}", inspect::summary(&world));
+            Ok(())
+        }
+        Commands::Sweep { seeds, name } => {
+            let constants = constants()?;
+            for seed in parse_seeds(&seeds)? {
+                let mut constants = constants.clone();
+                constants.seed = seed;
+                let name = name.as_ref().map(|name| format!("{name}-seed{seed}"));
+                let keep = &LoadPolicy::Keep;
+                simulate(
+                    constants,
+                    None,
+                    None,
+                    keep,
+                    Steering::Brains,
+                    &cli.runs_dir,
+                    name,
+                )?;
+            }
+            Ok(())
+        }
+        Commands::Export { save, output } => {
+            let world = World::load_from_file(&save)?;
+            saves::export(&world, &output)
+        }
+        Commands::Import { input, save } => {
+            let world = saves::import(&input)?;
+            world.save_to_file(&save)
+        }
+    }
+}
+
+// the copy of the save a loaded run started from, in its directory
+const LOADED: &str = "loaded.bin";
+
+// runs a recorded run again next to it and compares the state hashes age by age
+fn verify(run: &str) -> Result<()> {
+    let recorded = RunDir::open(run)?;
+    let recorded_hashes = recorded.file(hashes::FILE);
+    if !Path::new(&recorded_hashes).exists() {
+        return Err(Error::Config {
+            field: "hash_state".to_string(),
+            message: format!("{run} was recorded without state hashes"),
+        });
+    }
+    let constants = Constants::from_file(&recorded.file("constants.json"))?;
+    let agent_log = recorded.file("agent.jsonl");
+    let steering = if Path::new(&agent_log).exists() {
+        Steering::Replay(agent_log)
+    } else {
+        Steering::Brains
+    };
+    // runs from before the copy was kept can only go back to the save itself
+    let load = recorded.manifest()?.loaded_from.map(|path| {
+        let copy = recorded.file(LOADED);
+        if Path::new(&copy).exists() {
+            copy
+        } else {
+            path
+        }
+    });
+    // earlier verifications are kept, each one gets its own directory
+    let base = format!("{}-verify", recorded.name());
+    let mut name = base.clone();
+    let mut count = 1;
+    while Path::new(&format!("{}/{name}", recorded.root())).exists() {
+        count += 1;
+        name = format!("{base}-{count}");
+    }
+    let rerun = simulate(
+        constants,
+        load,
+        None,
+        &LoadPolicy::Replace,
+        steering,
+        &recorded.root(),
+        Some(name),
+    )?;
+
+    let recorded = hashes::read(&recorded_hashes)?;
+    let rerun = hashes::read(&rerun.file(hashes::FILE))?;
+    if let Some((age, detail)) = hashes::first_divergence(&recorded, &rerun) {
+        return Err(Error::Diverged { age, detail });
+    }
+    println!("identical at all {} ages", recorded.len());
+    Ok(())
+}
+
+// What drives the controlled blobs
+enum Steering {
+    // their own brains, as every other blob
+    Brains,
+    // an external agent, run by this shell command
+    Agent(String),
+    // the actions recorded in this agent log
+    Replay(String),
+}
+
+// The agent's log goes into the run directory. Resuming from a checkpoint, the
+// log is cut back to it and a replay starts at its age.
+fn controller(
+    steering: Steering,
+    run_dir: &RunDir,
+    resume: Option<&Checkpoint>,
+) -> Result<Option<Box<dyn Controller>>> {
+    match steering {
+        Steering::Brains => Ok(None),
+        Steering::Agent(command) => {
+            let log_path = run_dir.file("agent.jsonl");
+            // a run steered by brains until now has no log to go on from
+            let resume_at = resume
+                .map(|checkpoint| checkpoint.outputs.agent)
+                .filter(|_| Path::new(&log_path).exists());
+            let log = open_output(&log_path, resume_at).map_err(|e| Error::io(&log_path, e))?;
+            Ok(Some(Box::new(
+                ProcessAgent::spawn(&command, log).map_err(Error::Agent)?,
+            )))
+        }
+        Steering::Replay(log_path) => {
+            let replay = match resume {
+                Some(checkpoint) => ReplayAgent::open_at(&log_path, checkpoint.age),
+                // a replayed run keeps the log it was steered by, to be verified like any other
+                None => {
+                    let copy = run_dir.file("agent.jsonl");
+                    fs::copy(&log_path, &copy).map_err(|e| Error::io(&log_path, e))?;
+                    ReplayAgent::open(&copy)
+                }
+            };
+            Ok(Some(Box::new(replay.map_err(|e| Error::io(&log_path, e))?)))
+        }
+    }
+}
+
+fn simulate(
+    constants: Constants,
+    load: Option<String>,
+    save: Option<String>,
+    policy: &LoadPolicy,
+    steering: Steering,
+    runs_dir: &str,
+    name: Option<String>,
+) -> Result<RunDir> {
     println!("Hello, blobworld!");
-    let cli = Cli::parse();
-    let constants = load_constants();
-
-    let mut rng = StdRng::seed_from_u64(constants.seed as u64);
-    let (input_filename, output_filename) = parse_command(cli.command);
-    let mut world = World::load_or_start(input_filename, &mut rng);
-    world.evolve(&mut rng);
-    world.if_save(output_filename);
+    let mut rng = WorldRng::seed_from_u64(constants.seed as u64);
+    let loaded_from = load.clone();
+    let mut world = World::load_or_start(load, constants, policy, &mut rng)?;
+    // loading draws nothing, but the policy may have settled on another seed
+    if loaded_from.is_some() {
+        rng = WorldRng::seed_from_u64(world.constants.seed as u64);
+    }
+    let seed = world.constants.seed;
+    let run_dir = RunDir::create(runs_dir, name.as_deref(), seed, loaded_from.as_deref())?;
+    println!("writing to {}", run_dir.path);
+    // the save may be overwritten later, e.g. by this very run, so it is verified from a copy
+    if let Some(load) = &loaded_from {
+        let copy = run_dir.file(LOADED);
+        fs::copy(load, &copy).map_err(|e| Error::io(load, e))?;
+    }
+
+    let effective = world.constants.to_json()?;
+    println!("constants:\n{effective}");
+    let path = run_dir.file("constants.json");
+    fs::write(&path, effective).map_err(|e| Error::io(&path, e))?;
+
+    // whatever happens from here on is recorded in the manifest
+    let outcome = controller(steering, &run_dir, None)
+        .and_then(|mut controller| world.evolve(&run_dir, &mut rng, controller.as_deref_mut()));
+    run_dir.finish(&outcome)?;
+    outcome?;
+    world.if_save(save)?;
+    Ok(run_dir)
 }
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Seek, Write};
+use std::iter::Peekable;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use crate::mods::blobs::BlobType;
+
+// Wire format: for every controlled blob, in blob order, one `Observation` JSON
+// line goes out and one `Action` JSON line is expected back. Actions may be
+// written as `{"speed": s, "turn": t}` or simply `[s, t]`.
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Observation {
+    pub age: i32,
+    pub agent: usize,
+    pub blob_type: BlobType,
+    pub energy: f32,
+    pub stimuli: Vec<f32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Action {
+    pub speed: f32,
+    pub turn: f32,
+}
+
+// One exchange as written to (and read back from) the agent log
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Exchange {
+    pub observation: Observation,
+    pub action: Action,
+}
+
+pub trait Controller {
+    fn act(&mut self, observation: &Observation) -> io::Result<Action>;
+
+    // bytes of agent log written so far, for controllers that write one
+    fn log_len(&mut self) -> io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+fn invalid_data(err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Drives controlled blobs from a subprocess talking JSON lines over its stdin/stdout,
+/// recording every exchange so the run can be replayed without it.
+#[derive(Debug)]
+pub struct ProcessAgent {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+    stdout: BufReader<ChildStdout>,
+    log: BufWriter<File>,
+}
+
+impl ProcessAgent {
+    // the exchanges are appended to `log`, fresh or cut back to a checkpoint
+    pub fn spawn(command: &str, log: File) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("child stdin is piped");
+        let stdout = child.stdout.take().expect("child stdout is piped");
+        Ok(Self {
+            child,
+            stdin: Some(BufWriter::new(stdin)),
+            stdout: BufReader::new(stdout),
+            log: BufWriter::new(log),
+        })
+    }
+}
+
+impl Controller for ProcessAgent {
+    fn act(&mut self, observation: &Observation) -> io::Result<Action> {
+        let stdin = self.stdin.as_mut().expect("agent stdin already closed");
+        serde_json::to_writer(&mut *stdin, observation).map_err(invalid_data)?;
+        stdin.write_all(b"\n")?;
+        stdin.flush()?;
+
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "agent closed its stdout",
+            ));
+        }
+        let action: Action = serde_json::from_str(&line).map_err(invalid_data)?;
+
+        let exchange = Exchange {
+            observation: observation.clone(),
+            action,
+        };
+        serde_json::to_writer(&mut self.log, &exchange).map_err(invalid_data)?;
+        self.log.write_all(b"\n")?;
+        Ok(action)
+    }
+
+    fn log_len(&mut self) -> io::Result<Option<u64>> {
+        self.log.stream_position().map(Some)
+    }
+}
+
+impl Drop for ProcessAgent {
+    fn drop(&mut self) {
+        let _ = self.log.flush();
+        // closing stdin is the agent's signal that the run is over
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+/// Feeds back the actions recorded by a `ProcessAgent`, checking that the simulation
+/// asks for them in the same order and with the same observations.
+#[derive(Debug)]
+pub struct ReplayAgent {
+    lines: Peekable<Lines<BufReader<File>>>,
+    // bytes of the log used up, which is as far as the agent had written by then
+    read: u64,
+}
+
+impl ReplayAgent {
+    pub fn open(log_path: &str) -> io::Result<Self> {
+        Ok(Self {
+            lines: BufReader::new(File::open(log_path)?).lines().peekable(),
+            read: 0,
+        })
+    }
+
+    // the log from `age` on, for a run resumed from a checkpoint taken then
+    pub fn open_at(log_path: &str, age: i32) -> io::Result<Self> {
+        let mut replay = ReplayAgent::open(log_path)?;
+        while let Some(Ok(line)) = replay.lines.peek() {
+            let exchange: Exchange = serde_json::from_str(line).map_err(invalid_data)?;
+            if exchange.observation.age >= age {
+                break;
+            }
+            replay.read += line.len() as u64 + 1;
+            replay.lines.next();
+        }
+        Ok(replay)
+    }
+}
+
+impl Controller for ReplayAgent {
+    fn act(&mut self, observation: &Observation) -> io::Result<Action> {
+        let line = self.lines.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "agent log ran out of actions")
+        })??;
+        self.read += line.len() as u64 + 1;
+        let exchange: Exchange = serde_json::from_str(&line).map_err(invalid_data)?;
+        if exchange.observation != *observation {
+            return Err(invalid_data(format!(
+                "replay diverged at age {} agent {}",
+                observation.age, observation.agent
+            )));
+        }
+        Ok(exchange.action)
+    }
+
+    fn log_len(&mut self) -> io::Result<Option<u64>> {
+        Ok(Some(self.read))
+    }
+}
@@ -1,5 +1,7 @@
-use super::utils::visual_neuron_activation;
+use super::utils::{heard_signals, visual_neuron_activation};
 use crate::mods::brains::Brain;
+use crate::mods::constants::Constants;
+use crate::mods::genomes::{Genome, Mutation};
 use crate::mods::utils::cap;
 use crate::mods::world::World;
 use rand::Rng;
@@ -7,11 +9,25 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Blob {
+    pub genome: Genome,
     pub brain: Brain,
     pub position: (f32, f32),
     pub angle: f32,
     pub blob_type: BlobType,
     pub energy: f32,
+    // slot of the external agent steering this blob, if any
+    pub agent: Option<usize>,
+    // what the blob is broadcasting on each signal channel, set by its brain every age
+    pub signal: Vec<f32>,
+    // ages left digesting the last kill, a predator can't attack until it reaches 0
+    pub handling: i32,
+    // prey energy eaten and not yet digested
+    pub satiety: f32,
+    // unique within a world and never reused; founders have no parent
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub birth_age: i32,
+    pub generation: u32,
 }
 #[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
 pub enum BlobType {
@@ -21,6 +37,7 @@ pub enum BlobType {
 
 impl Blob {
     pub fn new(
+        genome: Genome,
         brain: Brain,
         position: (f32, f32),
         angle: f32,
@@ -28,11 +45,20 @@ impl Blob {
         init_energy: f32,
     ) -> Blob {
         Blob {
+            genome,
             brain,
             position,
             angle,
             blob_type,
             energy: init_energy,
+            agent: None,
+            signal: Vec::new(),
+            handling: 0,
+            satiety: 0.0,
+            id: 0,
+            parent: None,
+            birth_age: 0,
+            generation: 0,
         }
     }
 
@@ -40,15 +66,29 @@ impl Blob {
         (self.angle.cos(), self.angle.sin())
     }
 
+    // `ids` are the children's, `age` is their birth age
     pub fn reproduce(
         self,
         reproduction_distance: f32,
-        mutation_rate: f32,
+        mutation: &Mutation,
+        ids: (u64, u64),
+        age: i32,
         rng: &mut impl Rng,
     ) -> (Blob, Blob) {
         let direction = self.direction();
+        let parent = if mutation.inherit_learned {
+            Genome {
+                weights: self.brain.weights.clone(),
+                ..self.genome.clone()
+            }
+        } else {
+            self.genome.clone()
+        };
+        let genome1 = parent.mutate(mutation, rng);
+        let genome2 = parent.mutate(mutation, rng);
         let child1 = Blob {
-            brain: self.brain.make_child(mutation_rate, rng),
+            brain: self.brain.make_child(&genome1),
+            genome: genome1,
             position: (
                 self.position.0 - reproduction_distance * self.radius() * direction.0,
                 self.position.1 - reproduction_distance * self.radius() * direction.1,
@@ -56,9 +96,19 @@ impl Blob {
             angle: self.angle + std::f32::consts::FRAC_PI_2,
             blob_type: self.blob_type,
             energy: self.energy / 2.,
+            agent: None,
+            signal: Vec::new(),
+            handling: 0,
+            satiety: 0.0,
+            id: ids.0,
+            parent: Some(self.id),
+            birth_age: age,
+            generation: self.generation + 1,
         };
+        // the child keeping the parent's heading also keeps its external agent
         let child2 = Blob {
-            brain: self.brain.make_child(mutation_rate, rng),
+            brain: self.brain.make_child(&genome2),
+            genome: genome2,
             position: (
                 self.position.0 + reproduction_distance * self.radius() * direction.0,
                 self.position.1 + reproduction_distance * self.radius() * direction.1,
@@ -66,6 +116,14 @@ impl Blob {
             angle: self.angle,
             blob_type: self.blob_type,
             energy: self.energy / 2.,
+            agent: self.agent,
+            signal: Vec::new(),
+            handling: 0,
+            satiety: 0.0,
+            id: ids.1,
+            parent: Some(self.id),
+            birth_age: age,
+            generation: self.generation + 1,
         };
         (child1, child2)
     }
@@ -93,8 +151,8 @@ impl Blob {
         let mut stimuli = Vec::new();
         match self.blob_type {
             BlobType::Predator => {
-                for neuron in 0..self.brain.network_shape[0] {
-                    let neuron_angle = self.brain.neuron_angles[neuron as usize] + self.angle;
+                for neuron_angle in &self.brain.neuron_angles {
+                    let neuron_angle = neuron_angle + self.angle;
                     let neuron_vec = (neuron_angle.cos(), neuron_angle.sin());
                     let activation = visual_neuron_activation(
                         &preys,
@@ -106,8 +164,8 @@ impl Blob {
                 }
             }
             BlobType::Prey => {
-                for neuron in 0..self.brain.network_shape[0] {
-                    let neuron_angle = self.brain.neuron_angles[neuron as usize] + self.angle;
+                for neuron_angle in &self.brain.neuron_angles {
+                    let neuron_angle = neuron_angle + self.angle;
                     let neuron_vec = (neuron_angle.cos(), neuron_angle.sin());
                     let activation = visual_neuron_activation(
                         &predators,
@@ -119,15 +177,53 @@ impl Blob {
                 }
             }
         }
+        stimuli.extend(heard_signals(
+            &world.blobs,
+            self,
+            world.constants.signal_channels as usize,
+            world.constants.signal_range,
+        ));
+        // scent gradients in the blob's own frame: ahead of it and to its left
+        let direction = self.direction();
+        for field in &world.pheromones {
+            let (gx, gy) = field.gradient(self.position);
+            stimuli.push(gx * direction.0 + gy * direction.1);
+            stimuli.push(gy * direction.0 - gx * direction.1);
+        }
+        if world.constants.handling_time > 0 {
+            stimuli.push(self.handling as f32 / world.constants.handling_time as f32);
+        }
         stimuli
     }
 
+    pub fn can_attack(&self, satiation_cap: f32) -> bool {
+        self.handling == 0 && (satiation_cap <= 0.0 || self.satiety < satiation_cap)
+    }
+
     pub fn add_energy(&mut self, energy: f32) {
         self.energy += energy;
     }
 
     pub fn radius(&self) -> f32 {
-        self.energy.sqrt()
+        (self.genome.body.body_mass + self.energy).sqrt()
+    }
+
+    // energy spent every age just to keep the body's traits
+    pub fn upkeep(&self, constants: &Constants) -> f32 {
+        let body = &self.genome.body;
+        constants.speed_upkeep * body.top_speed
+            + constants.turn_upkeep * body.turn_rate
+            + constants.metabolism_upkeep * body.metabolism.powi(2)
+    }
+
+    // odds of a kill grow with the predator/prey size ratio: even at equal size,
+    // certain for a much bigger predator, hopeless against a much bigger prey
+    pub fn kill_probability(&self, prey: &Blob, size_exponent: f32) -> f32 {
+        let odds = (self.radius() / prey.radius()).powf(size_exponent);
+        if odds.is_infinite() {
+            return 1.0;
+        }
+        odds / (1.0 + odds)
     }
     pub fn die(self) {}
 }
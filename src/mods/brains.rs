@@ -1,8 +1,27 @@
 use super::activations::Activation;
-use crate::mods::utils::{matrix_prod, sum_weights};
+use super::constants::Constants;
+use super::genomes::Genome;
+use crate::mods::utils::matrix_prod;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+// Whether weights change with activity during a lifetime, and if so whether the
+// learned weights or the inborn ones are passed on
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum Plasticity {
+    None,
+    Baldwinian,
+    Lamarckian,
+}
+
+pub fn which_plasticity(constants: &Constants) -> Plasticity {
+    match constants.plasticity.as_str() {
+        "baldwinian" => Plasticity::Baldwinian,
+        "lamarckian" => Plasticity::Lamarckian,
+        _ => Plasticity::None,
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Brain {
     pub network_shape: Vec<i32>,
@@ -14,19 +33,20 @@ pub struct Brain {
 }
 
 impl Brain {
+    // wires up the brain a genome describes; the weights start as the genome's
     pub fn new(
+        genome: &Genome,
         network_shape: Vec<i32>,
-        neuron_separation_radians: f32,
+        vision_neurons: i32,
         neuron_length: f32,
-        weights: Option<Vec<Vec<Vec<f32>>>>,
         activation: Activation,
-        rng: &mut impl Rng,
     ) -> Brain {
-        let weights = weights.unwrap_or(Self::init_random(&network_shape, rng));
+        let neuron_separation_radians = genome.neuron_separation_radians;
+        let weights = genome.weights.clone();
         let mut neuron_angles = Vec::new();
-        let input_neurons = network_shape[0];
-        for i in 0..input_neurons {
-            neuron_angles.push((i - input_neurons / 2) as f32 * neuron_separation_radians)
+        // inputs past the vision rays are non-visual senses and get no angle
+        for i in 0..vision_neurons {
+            neuron_angles.push((i - vision_neurons / 2) as f32 * neuron_separation_radians)
         }
 
         Brain {
@@ -39,7 +59,7 @@ impl Brain {
         }
     }
 
-    pub fn init_random(network_shape: &Vec<i32>, rng: &mut impl Rng) -> Vec<Vec<Vec<f32>>> {
+    pub fn init_random(network_shape: &[i32], rng: &mut impl Rng) -> Vec<Vec<Vec<f32>>> {
         let mut weights = Vec::new();
         for layer in 0..network_shape.len() - 1 {
             let mut weight_matrix = Vec::new();
@@ -55,52 +75,47 @@ impl Brain {
         weights
     }
 
-    pub fn delta(
-        network_shape: &Vec<i32>,
-        mutation_rate: f32,
-        rng: &mut impl Rng,
-    ) -> Vec<Vec<Vec<f32>>> {
-        let mut weights = Vec::new();
-        for layer in 0..network_shape.len() - 1 {
-            let mut weight_matrix = Vec::new();
-            for _post_synaptic_neuron in 0..network_shape[layer + 1] {
-                let mut weight_vector = Vec::new();
-                for _pre_synaptic_neuron in 0..network_shape[layer] {
-                    weight_vector.push(mutation_rate * rng.gen_range(-1.0..1.0));
-                }
-                weight_matrix.push(weight_vector);
-            }
-            weights.push(weight_matrix);
-        }
-        weights
-    }
-
-    pub fn make_child(&self, mutation_rate: f32, rng: &mut impl Rng) -> Brain {
-        let weights = sum_weights(
-            &self.weights,
-            &Brain::delta(&self.network_shape, mutation_rate, rng),
-        );
-        let new_separation = 0.0_f32.max(
-            std::f32::consts::TAU.min(self.neuron_separation_radians + rng.gen_range(-0.01..0.01)),
-        );
+    // a brain wired like this one, grown from a child's genome
+    pub fn make_child(&self, genome: &Genome) -> Brain {
         Brain::new(
+            genome,
             self.network_shape.clone(),
-            new_separation,
+            self.neuron_angles.len() as i32,
             self.neuron_length,
-            Some(weights),
             self.activation.clone(),
-            rng,
         )
     }
 
-    pub fn synapse(&self, stimuli: &Vec<f32>) -> Vec<f32> {
-        let mut input = stimuli.clone();
+    // the values of every layer, from the stimuli to the (unsquashed) outputs
+    pub fn activity(&self, stimuli: &[f32]) -> Vec<Vec<f32>> {
+        let mut layers = vec![stimuli.to_vec()];
         for layer in 0..self.network_shape.len() - 1 {
-            input = matrix_prod(&self.weights[layer], &input);
+            let mut input = matrix_prod(&self.weights[layer], &layers[layer]);
             if layer != self.network_shape.len() - 2 {
                 input = input.iter().map(|&x| self.activation.apply(x)).collect();
             }
+            layers.push(input);
+        }
+        layers
+    }
+
+    // Hebb's rule with a heritable coefficient per connection:
+    // dw = rate * coefficient * pre * post, kept within +-bound
+    pub fn learn(
+        &mut self,
+        plasticity: &[Vec<Vec<f32>>],
+        activity: &[Vec<f32>],
+        rate: f32,
+        bound: f32,
+    ) {
+        for (layer, matrix) in self.weights.iter_mut().enumerate() {
+            let (pre, post) = (&activity[layer], &activity[layer + 1]);
+            for (i, row) in matrix.iter_mut().enumerate() {
+                for (j, weight) in row.iter_mut().enumerate() {
+                    let dw = rate * plasticity[layer][i][j] * pre[j] * post[i];
+                    *weight = (*weight + dw).clamp(-bound, bound);
+                }
+            }
         }
-        input
     }
 }
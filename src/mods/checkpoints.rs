@@ -0,0 +1,136 @@
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::mods::errors::{Error, Result};
+use crate::mods::saves::VERSION;
+use crate::mods::world::World;
+
+// A checkpoint file is MAGIC and the save format version of the world in it, as a
+// little-endian u32, then the bincode of the Checkpoint
+pub const MAGIC: &[u8; 8] = b"BLOBCKPT";
+
+// The generator behind StdRng, so seeds give the same runs, but one whose state
+// can be written into a checkpoint
+pub type WorldRng = ChaCha12Rng;
+
+// How many bytes of each run output had been written
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct OutputLengths {
+    pub log: u64,
+    pub frames: u64,
+    pub events: u64,
+    pub hashes: u64,
+    pub agent: u64,
+}
+
+/// Everything needed to carry on a run exactly where it was: the world, the RNG,
+/// the next age to simulate and how far the outputs had got by then.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub world: World,
+    pub rng: WorldRng,
+    pub age: i32,
+    pub outputs: OutputLengths,
+}
+
+impl Checkpoint {
+    fn path(dir: &str, age: i32) -> String {
+        format!("{dir}/{age:06}.ckpt")
+    }
+
+    // (age, path) of every checkpoint in the directory, oldest first
+    fn list(dir: &str) -> Result<Vec<(i32, String)>> {
+        Checkpoint::list_in_dir(dir).map_err(|e| Error::io(dir, e))
+    }
+
+    fn list_in_dir(dir: &str) -> io::Result<Vec<(i32, String)>> {
+        let mut checkpoints = Vec::new();
+        if !fs::exists(dir)? {
+            return Ok(checkpoints);
+        }
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            let age = name.strip_suffix(".ckpt").and_then(|age| age.parse().ok());
+            if let Some(age) = age {
+                checkpoints.push((age, format!("{dir}/{name}")));
+            }
+        }
+        checkpoints.sort();
+        Ok(checkpoints)
+    }
+
+    pub fn latest(dir: &str) -> Result<String> {
+        Checkpoint::list(dir)?
+            .pop()
+            .map(|(_, path)| path)
+            .ok_or(Error::NoCheckpoint {
+                path: dir.to_string(),
+            })
+    }
+
+    /// Writes the checkpoint into `dir` and deletes all but the `kept` newest ones
+    /// there. It goes through a temporary file so a crash never leaves half a checkpoint.
+    pub fn save(&self, dir: &str, kept: usize) -> Result<()> {
+        fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        let path = Checkpoint::path(dir, self.age);
+        let temporary = format!("{path}.tmp");
+        let write = || -> io::Result<()> {
+            let mut encoded = MAGIC.to_vec();
+            encoded.extend(VERSION.to_le_bytes());
+            bincode::serialize_into(&mut encoded, self).map_err(io::Error::other)?;
+            File::create(&temporary)?.write_all(&encoded)?;
+            fs::rename(&temporary, &path)
+        };
+        write().map_err(|e| Error::io(&path, e))?;
+
+        let checkpoints = Checkpoint::list(dir)?;
+        let old = checkpoints.len().saturating_sub(kept.max(1));
+        for (_, path) in &checkpoints[..old] {
+            fs::remove_file(path).map_err(|e| Error::io(path, e))?;
+        }
+        Ok(())
+    }
+
+    // Checkpoints are for carrying on a run with the build that started it, so
+    // unlike saves there is no upgrading: any other version is refused
+    pub fn load(path: &str) -> Result<Checkpoint> {
+        let read = || -> io::Result<Checkpoint> {
+            let mut buffer = Vec::new();
+            File::open(path)?.read_to_end(&mut buffer)?;
+            let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+            let body = match buffer.strip_prefix(MAGIC) {
+                Some(rest) if rest.len() >= 4 => {
+                    let version = u32::from_le_bytes(rest[..4].try_into().unwrap());
+                    if version != VERSION {
+                        return Err(invalid(format!(
+                            "checkpoint format version {version}, this build reads {VERSION}"
+                        )));
+                    }
+                    &rest[4..]
+                }
+                _ => {
+                    return Err(invalid(
+                        "written before checkpoints were versioned".to_string(),
+                    ))
+                }
+            };
+            bincode::deserialize(body).map_err(|e| invalid(e.to_string()))
+        };
+        read().map_err(|e| Error::io(path, e))
+    }
+}
+
+// A fresh output file, or with `resume_at` the existing one cut back to that length
+pub fn open_output(path: &str, resume_at: Option<u64>) -> io::Result<File> {
+    match resume_at {
+        Some(len) => {
+            let mut file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(len)?;
+            file.seek(SeekFrom::End(0))?;
+            Ok(file)
+        }
+        None => File::create(path),
+    }
+}
@@ -1,38 +1,178 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::mods::constants::DEFAULT_PATH;
+use crate::mods::errors::{Error, Result};
+use crate::mods::runs::DEFAULT_ROOT;
+use crate::mods::stats::FRAME_COLUMN_SETS;
 
 #[derive(Parser)]
 #[command(name = "blobworld")]
 #[command(about = "A CLI for managing a world of blobs", long_about = None)]
 pub struct Cli {
+    /// What to do, `run` when left out
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Constants file to start from
+    #[arg(long, global = true, default_value = DEFAULT_PATH)]
+    pub config: String,
+
+    /// Override a constant, e.g. --set ages=500 --set activation=tanh
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Seed of the run, instead of the one in the constants file
+    #[arg(long, global = true)]
+    pub seed: Option<i32>,
+
+    /// Directory every run gets its own directory in
+    #[arg(long, global = true, default_value = DEFAULT_ROOT)]
+    pub runs_dir: String,
+}
+
+// What a loaded world does with the constants from --config and --set
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum OnLoad {
+    /// Run with the constants the world was saved with, --set and --seed are refused
+    #[default]
+    Keep,
+    /// Run with the constants file and --set overrides instead
+    Replace,
+    /// Run with the saved constants, changed only by --set and --seed
+    Override,
+}
+
+#[derive(Args, Default)]
+pub struct SaveArgs {
+    /// Start from a saved world instead of generating one
+    #[arg(long, value_name = "SAVE")]
+    pub load: Option<String>,
+
+    /// Which constants a loaded world runs with; every change is listed as a warning
+    #[arg(long, value_enum, default_value_t, requires = "load")]
+    pub on_load: OnLoad,
+
+    /// Save the world here when the run ends
+    #[arg(long, value_name = "SAVE")]
+    pub save: Option<String>,
+}
+
+#[derive(Args, Default)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub saves: SaveArgs,
+
+    /// Name of the run's directory instead of its start time and seed
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Shell command of an external agent steering the controlled blobs over stdin/stdout
+    #[arg(long)]
+    pub agent: Option<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    Save {
-        filename: String,
+    /// Simulate a world, fresh or loaded from a save
+    Run(RunArgs),
+    /// Continue a run from a checkpoint, by default the latest run of the seed from its last one
+    Resume {
+        /// A run directory, to go on from its last checkpoint, or a checkpoint file in one
+        run: Option<String>,
+
+        /// Shell command of the external agent the run was steered by
+        #[arg(long, conflicts_with = "replay")]
+        agent: Option<String>,
+
+        /// Agent log whose recorded actions steer the run instead of an agent
+        #[arg(long, value_name = "AGENT_LOG")]
+        replay: Option<String>,
+    },
+    /// Simulate again with the actions recorded in an agent log instead of running the agent
+    Replay {
+        agent_log: String,
+
+        #[command(flatten)]
+        saves: SaveArgs,
+
+        /// Name of the run's directory instead of its start time and seed
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Draw the frames of a frames file as PNG images, without simulating
+    ///
+    /// The world shape and pheromone shading come from the constants.json next to the
+    /// frames, as in a run directory, and otherwise from --config.
+    Render {
+        frames: String,
+
+        /// Directory the images go to
+        #[arg(long, default_value = "animation")]
+        out_dir: String,
+
+        /// Draw only every this many ages
+        #[arg(long, default_value_t = 1)]
+        every: usize,
     },
-    Load {
-        filename: String,
+    /// Print the per-age statistics a frames file holds as CSV, without simulating
+    Stats {
+        frames: String,
+
+        /// Column sets to write
+        #[arg(long, value_delimiter = ',', default_values = FRAME_COLUMN_SETS)]
+        columns: Vec<String>,
+
+        /// File to write the CSV to instead of stdout
+        #[arg(long)]
+        output: Option<String>,
     },
-    LoadAndSave {
-        input_filename: String,
-        output_filename: String,
+    /// Run a recorded run again into RUN-verify and report the first age its state hashes differ
+    ///
+    /// The run must have been made with hash_state on. It is repeated with its own
+    /// constants, from its copy of the save if it was loaded, replaying its agent log if it had one.
+    Verify { run: String },
+    /// Print a summary of a saved world without running it
+    Inspect { save: String },
+    /// Run the same constants once per seed, e.g. `sweep 1-10` or `sweep 3,7,11`
+    Sweep {
+        seeds: String,
+
+        /// Name the run directories NAME-seed<seed> instead of by start time
+        #[arg(long)]
+        name: Option<String>,
     },
+    /// Write a saved world as pretty JSON or RON, chosen by the output's extension
+    Export { save: String, output: String },
+    /// Turn a JSON or RON export back into a saved world
+    Import { input: String, save: String },
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Commands::Run(RunArgs::default())
+    }
 }
 
-pub fn parse_command(cli_command: Option<Commands>) -> (Option<String>, Option<String>) {
-    if let Some(command) = cli_command {
-        match command {
-            Commands::Save { filename } => (None, Some(filename)),
-            Commands::Load { filename } => (Some(filename), None),
-            Commands::LoadAndSave {
-                input_filename,
-                output_filename,
-            } => (Some(input_filename), Some(output_filename)),
+// Comma separated seeds and inclusive ranges, e.g. "1-5,9"
+pub fn parse_seeds(spec: &str) -> Result<Vec<i32>> {
+    let invalid = |part: &str| Error::Config {
+        field: "seeds".to_string(),
+        message: format!("{part:?} is not a seed or a range like 1-10"),
+    };
+    let mut seeds = Vec::new();
+    for part in spec.split(',').map(str::trim) {
+        // a leading '-' is a negative seed, not a range
+        match part.char_indices().skip(1).find(|&(_, c)| c == '-') {
+            Some((dash, _)) => {
+                let start: i32 = part[..dash].parse().map_err(|_| invalid(part))?;
+                let end: i32 = part[dash + 1..].parse().map_err(|_| invalid(part))?;
+                if end < start {
+                    return Err(invalid(part));
+                }
+                seeds.extend(start..=end);
+            }
+            None => seeds.push(part.parse().map_err(|_| invalid(part))?),
         }
-    } else {
-        (None, None)
     }
+    Ok(seeds)
 }
@@ -2,6 +2,10 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs;
 
+use crate::mods::brains::{which_plasticity, Plasticity};
+use crate::mods::errors::{Error, Result};
+use crate::mods::stats::COLUMN_SETS;
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Constants {
     pub seed: i32,
@@ -24,17 +28,291 @@ pub struct Constants {
     pub activation: String,
     pub render_every: i32,
     pub dump_frames: bool,
+    pub controlled_prey: i32,
+    pub controlled_predators: i32,
+    pub signal_channels: i32,
+    pub signal_range: f32,
+    pub signal_energy_cost: f32,
+    pub pheromones: bool,
+    pub pheromone_cell_size: f32,
+    pub pheromone_diffusion: f32,
+    pub pheromone_decay: f32,
+    pub pheromone_deposit: f32,
+    pub graph_pheromones: bool,
+    pub size_dependent_predation: bool,
+    pub predation_size_exponent: f32,
+    pub failed_attack_cost: f32,
+    pub predation_injury: f32,
+    pub handling_time: i32,
+    pub satiation_cap: f32,
+    pub digestion_rate: f32,
+    pub mutation_operator: String,
+    pub gene_mutation_probability: f32,
+    pub large_mutation_probability: f32,
+    pub large_mutation_scale: f32,
+    pub weight_bound: f32,
+    pub self_adaptive_mutation: bool,
+    pub mutation_step_tau: f32,
+    pub min_mutation_step: f32,
+    pub heritable_body: bool,
+    pub body_mutation_rate: f32,
+    pub speed_upkeep: f32,
+    pub turn_upkeep: f32,
+    pub metabolism_upkeep: f32,
+    pub plasticity: String,
+    pub hebbian_rate: f32,
+    pub plastic_weight_bound: f32,
+    pub phylogeny: bool,
+    pub prune_phylogeny: bool,
+    pub log_events: bool,
+    pub log_columns: Vec<String>,
+    pub checkpoint_every: i32,
+    pub checkpoints_kept: usize,
+    pub hash_state: bool,
+}
+
+// The values constants.json ships with, so a world can be set up in code without any file
+impl Default for Constants {
+    fn default() -> Self {
+        Constants {
+            seed: 12,
+            reproduction_distance: 1.0,
+            food_energy: 1.0,
+            step_size: 0.5,
+            neuron_length: 20.0,
+            world_shape: (341.5, 192.0),
+            input_neurons_num: 30,
+            motion_energy_cost: 0.007,
+            prey_base_energy_gain: 0.03,
+            predator_base_energy_loss: 0.004,
+            mutation_rate: 0.2,
+            ages: 2000,
+            num_predators: 50,
+            num_prey: 100,
+            max_speed: 5.0,
+            max_angle_diff: 0.3,
+            graph_neurons: false,
+            activation: "relu".to_string(),
+            render_every: 0,
+            dump_frames: true,
+            controlled_prey: 0,
+            controlled_predators: 0,
+            signal_channels: 0,
+            signal_range: 30.0,
+            signal_energy_cost: 0.001,
+            pheromones: false,
+            pheromone_cell_size: 8.0,
+            pheromone_diffusion: 0.1,
+            pheromone_decay: 0.02,
+            pheromone_deposit: 0.1,
+            graph_pheromones: false,
+            size_dependent_predation: false,
+            predation_size_exponent: 2.0,
+            failed_attack_cost: 0.05,
+            predation_injury: 0.1,
+            handling_time: 0,
+            satiation_cap: 0.0,
+            digestion_rate: 0.05,
+            mutation_operator: "uniform".to_string(),
+            gene_mutation_probability: 1.0,
+            large_mutation_probability: 0.0,
+            large_mutation_scale: 10.0,
+            weight_bound: 0.0,
+            self_adaptive_mutation: false,
+            mutation_step_tau: 0.2,
+            min_mutation_step: 0.001,
+            heritable_body: false,
+            body_mutation_rate: 0.05,
+            speed_upkeep: 0.0002,
+            turn_upkeep: 0.001,
+            metabolism_upkeep: 0.001,
+            plasticity: "none".to_string(),
+            hebbian_rate: 0.01,
+            plastic_weight_bound: 5.0,
+            phylogeny: false,
+            prune_phylogeny: false,
+            log_events: false,
+            log_columns: COLUMN_SETS.iter().map(|set| set.to_string()).collect(),
+            checkpoint_every: 0,
+            checkpoints_kept: 3,
+            hash_state: false,
+        }
+    }
 }
 
 impl Constants {
-    pub fn from_file(path: &str) -> serde_json::Result<Constants> {
-        let data = fs::read_to_string(path).expect("Unable to read file");
-        let constants: Constants = serde_json::from_str(&data)?;
+    pub fn from_file(path: &str) -> Result<Constants> {
+        let data = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let constants: Constants = serde_json::from_str(&data).map_err(|e| Error::json(path, e))?;
+        constants.validate()?;
         Ok(constants)
     }
+
+    // values that parse but that the simulation can't run with
+    pub fn validate(&self) -> Result<()> {
+        let out_of_range = |field: &str, message: &str| Error::Config {
+            field: field.to_string(),
+            message: message.to_string(),
+        };
+        // PheromoneField::step is an explicit scheme, unstable past a quarter per neighbour
+        if !(0.0..=0.25).contains(&self.pheromone_diffusion) {
+            return Err(out_of_range(
+                "pheromone_diffusion",
+                "must be between 0 and 0.25 for diffusion to stay stable",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.pheromone_decay) {
+            return Err(out_of_range(
+                "pheromone_decay",
+                "is the fraction lost each age, between 0 and 1",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::json("constants", e))
+    }
+
+    /// Applies `key=value` overrides. The value is read as JSON, or taken as a plain
+    /// string when it isn't JSON, so `activation=tanh` works as well as `ages=500`.
+    pub fn with_overrides(self, overrides: &[String]) -> Result<Constants> {
+        let mut fields = serde_json::to_value(self).map_err(|e| Error::json("constants", e))?;
+        for assignment in overrides {
+            let (key, value) = assignment.split_once('=').ok_or_else(|| Error::Config {
+                field: assignment.clone(),
+                message: "overrides are written key=value".to_string(),
+            })?;
+            let field = fields.get_mut(key).ok_or_else(|| Error::Config {
+                field: key.to_string(),
+                message: "no such constant".to_string(),
+            })?;
+            *field = serde_json::from_str(value)
+                .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+            // checked one at a time so the error can name the culprit
+            serde_json::from_value::<Constants>(fields.clone()).map_err(|e| Error::Config {
+                field: key.to_string(),
+                message: e.to_string(),
+            })?;
+        }
+        let constants: Constants =
+            serde_json::from_value(fields).map_err(|e| Error::json("constants", e))?;
+        constants.validate()?;
+        Ok(constants)
+    }
+
+    // (name, value) of every constant, f32s printed as f32s rather than the f64s JSON holds
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(self) else {
+            return Vec::new();
+        };
+        fields
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value.as_f64() {
+                    Some(number) if value.is_f64() => (number as f32).to_string(),
+                    _ => value.to_string(),
+                };
+                (key, value)
+            })
+            .collect()
+    }
+
+    // (name, old, new) of every constant that differs from `old`
+    pub fn changes_from(&self, old: &Constants) -> Vec<(String, String, String)> {
+        old.fields()
+            .into_iter()
+            .zip(self.fields())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((key, old), (_, new))| (key, old, new))
+            .collect()
+    }
+
+    // inputs: vision rays, one heard value per signal channel, the forward and lateral
+    // scent gradient of each species' field, then the blob's own handling timer
+    // outputs: speed, turn, one value per signal channel, then the scent deposit
+    pub fn network_shape(&self) -> Vec<i32> {
+        let (scent_inputs, scent_outputs) = if self.pheromones { (4, 1) } else { (0, 0) };
+        let handling_inputs = if self.handling_time > 0 { 1 } else { 0 };
+        vec![
+            self.input_neurons_num + self.signal_channels + scent_inputs + handling_inputs,
+            2 + self.signal_channels + scent_outputs,
+        ]
+    }
+}
+
+/// What a world loaded from a save runs with.
+pub enum LoadPolicy {
+    // the constants it was saved with
+    Keep,
+    // the given ones, as a fresh world would get
+    Replace,
+    // the saved ones with these key=value overrides
+    Override(Vec<String>),
 }
 
-// Declare the constants variable
-pub fn load_constants() -> Constants {
-    Constants::from_file("constants.json").expect("Failed to load constants")
+impl LoadPolicy {
+    /// The constants for a world saved with `saved`, `given` being the ones a fresh
+    /// world would get. Every constant that ends up different is printed as a warning;
+    /// the world's shape, the brains' layout and whether they have plasticity
+    /// coefficients can't change under a saved population.
+    pub fn apply(&self, saved: Constants, given: Constants) -> Result<Constants> {
+        let constants = match self {
+            LoadPolicy::Keep => return Ok(saved),
+            LoadPolicy::Replace => given,
+            LoadPolicy::Override(overrides) => saved.clone().with_overrides(overrides)?,
+        };
+        if constants.world_shape != saved.world_shape {
+            return Err(Error::Config {
+                field: "world_shape".to_string(),
+                message: "a saved world keeps its shape".to_string(),
+            });
+        }
+        if constants.network_shape() != saved.network_shape() {
+            return Err(Error::Config {
+                field: "network_shape".to_string(),
+                message: format!(
+                    "the saved brains are {:?}, these constants make {:?}; \
+                     input_neurons_num, signal_channels, pheromones and handling_time must stay as saved",
+                    saved.network_shape(),
+                    constants.network_shape()
+                ),
+            });
+        }
+        // genomes saved without plasticity have no learning coefficients to learn with
+        let plastic =
+            |constants: &Constants| !matches!(which_plasticity(constants), Plasticity::None);
+        if plastic(&constants) && !plastic(&saved) {
+            return Err(Error::Config {
+                field: "plasticity".to_string(),
+                message: format!(
+                    "the saved genomes have no plasticity coefficients, it must stay {:?}",
+                    saved.plasticity
+                ),
+            });
+        }
+        let changes = constants.changes_from(&saved);
+        if !changes.is_empty() {
+            eprintln!("warning: running the saved world with changed constants");
+            for (key, old, new) in changes {
+                eprintln!("  {key}: {old} -> {new}");
+            }
+        }
+        Ok(constants)
+    }
+}
+
+pub const DEFAULT_PATH: &str = "constants.json";
+
+// a small world that runs fast, for the tests to build theirs from
+#[cfg(test)]
+pub(crate) fn small_constants() -> Constants {
+    Constants {
+        world_shape: (60.0, 40.0),
+        num_prey: 12,
+        num_predators: 6,
+        ages: 5,
+        dump_frames: false,
+        ..Constants::default()
+    }
 }
@@ -0,0 +1,253 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io;
+
+use crate::mods::{
+    agents::{Action, Controller, Observation},
+    blobs::{Blob, BlobType},
+    constants::Constants,
+    errors::Result,
+    world::World,
+};
+
+// The agent's blob always gets the first agent slot
+const AGENT_SLOT: usize = 0;
+
+#[derive(Clone, Debug)]
+pub struct RewardWeights {
+    pub energy: f32,
+    pub kill: f32,
+    pub survival: f32,
+}
+
+impl Default for RewardWeights {
+    fn default() -> Self {
+        RewardWeights {
+            energy: 1.0,
+            kill: 1.0,
+            survival: 0.01,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StepResult {
+    pub observation: Vec<f32>,
+    pub reward: f32,
+    pub done: bool,
+}
+
+// Answers for the agent with whatever action was passed to `Env::step`
+struct FixedAction(Action);
+
+impl Controller for FixedAction {
+    fn act(&mut self, _observation: &Observation) -> io::Result<Action> {
+        Ok(self.0)
+    }
+}
+
+/// Single-agent reset/step interface over a `World`: one blob of `agent_type` is steered
+/// by the caller while every other blob keeps using its brain. The episode follows the
+/// agent's lineage (the agent slot passes to one child on reproduction) and ends when it
+/// dies, a species goes extinct or `constants.ages` is reached.
+#[derive(Debug)]
+pub struct Env {
+    pub world: World,
+    pub constants: Constants,
+    pub agent_type: BlobType,
+    pub rewards: RewardWeights,
+    rng: StdRng,
+    age: i32,
+    done: bool,
+}
+
+impl Env {
+    pub fn new(constants: Constants, agent_type: BlobType) -> Env {
+        let mut env = Env {
+            world: World::new(Vec::new(), constants.clone()),
+            constants,
+            agent_type,
+            rewards: RewardWeights::default(),
+            rng: StdRng::seed_from_u64(0),
+            age: 0,
+            done: true,
+        };
+        env.reset(env.constants.seed as u64);
+        env
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        let mut constants = self.constants.clone();
+        constants.seed = seed as i32;
+        // stepping must never touch the disk
+        constants.render_every = 0;
+        (constants.controlled_prey, constants.controlled_predators) = match self.agent_type {
+            BlobType::Prey => (1, 0),
+            BlobType::Predator => (0, 1),
+        };
+
+        self.rng = StdRng::seed_from_u64(seed);
+        self.world = World::generate(constants, &mut self.rng);
+        self.age = 0;
+        self.done = false;
+        self.observation()
+    }
+
+    pub fn step(&mut self, action: Action) -> Result<StepResult> {
+        assert!(
+            !self.done,
+            "step called on a finished episode, call reset first"
+        );
+        let energy_before = self.agent().map_or(0.0, |blob| blob.energy);
+
+        let mut controller = FixedAction(action);
+        let report = self
+            .world
+            .update(self.age, &mut self.rng, Some(&mut controller))?;
+        self.age += 1;
+
+        let outcome = report.agents.get(&AGENT_SLOT).cloned().unwrap_or_default();
+        let mut reward = self.rewards.kill * outcome.kills as f32;
+        if !outcome.died {
+            // after splitting, the agent's child holds only half the parent's energy
+            let energy_after = self.agent().map_or(0.0, |blob| blob.energy);
+            let energy_after = if outcome.reproduced {
+                2.0 * energy_after
+            } else {
+                energy_after
+            };
+            reward += self.rewards.energy * (energy_after - energy_before);
+            reward += self.rewards.survival;
+        }
+
+        let extinct = !self
+            .world
+            .blobs
+            .iter()
+            .any(|b| b.blob_type == BlobType::Prey)
+            || !self
+                .world
+                .blobs
+                .iter()
+                .any(|b| b.blob_type == BlobType::Predator);
+        self.done = outcome.died || extinct || self.age >= self.constants.ages;
+
+        Ok(StepResult {
+            observation: self.observation(),
+            reward,
+            done: self.done,
+        })
+    }
+
+    pub fn age(&self) -> i32 {
+        self.age
+    }
+
+    pub fn agent(&self) -> Option<&Blob> {
+        self.world
+            .blobs
+            .iter()
+            .find(|blob| blob.agent == Some(AGENT_SLOT))
+    }
+
+    // what the agent's blob currently sees, all zeros once it is gone
+    pub fn observation(&self) -> Vec<f32> {
+        match self.agent() {
+            Some(blob) => blob.check_surroundings(&self.world),
+            None => vec![0.0; self.constants.network_shape()[0] as usize],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::constants::small_constants;
+
+    fn idle() -> Action {
+        Action {
+            speed: 0.0,
+            turn: 0.0,
+        }
+    }
+
+    #[test]
+    fn reset_gives_the_agent_an_observation() {
+        let mut env = Env::new(small_constants(), BlobType::Prey);
+        let observation = env.reset(3);
+        assert_eq!(env.age(), 0);
+        assert_eq!(observation.len(), env.constants.network_shape()[0] as usize);
+        let agent = env.agent().expect("the agent's blob exists after reset");
+        assert_eq!(agent.blob_type, BlobType::Prey);
+    }
+
+    #[test]
+    fn same_seed_same_episode() {
+        let mut first = Env::new(small_constants(), BlobType::Predator);
+        let mut second = Env::new(small_constants(), BlobType::Predator);
+        assert_eq!(first.reset(7), second.reset(7));
+        for _ in 0..3 {
+            let a = first.step(idle()).unwrap();
+            let b = second.step(idle()).unwrap();
+            assert_eq!(a.observation, b.observation);
+            assert_eq!(a.reward, b.reward);
+            assert_eq!(a.done, b.done);
+        }
+    }
+
+    #[test]
+    fn episode_ends_at_the_last_age() {
+        let mut env = Env::new(small_constants(), BlobType::Prey);
+        env.reset(1);
+        let mut steps = 0;
+        loop {
+            let result = env.step(idle()).unwrap();
+            steps += 1;
+            assert_eq!(env.age(), steps);
+            if result.done {
+                break;
+            }
+        }
+        assert!(steps <= env.constants.ages);
+    }
+
+    #[test]
+    #[should_panic(expected = "call reset first")]
+    fn stepping_a_finished_episode_panics() {
+        let constants = Constants {
+            ages: 1,
+            ..small_constants()
+        };
+        let mut env = Env::new(constants, BlobType::Prey);
+        env.reset(1);
+        assert!(env.step(idle()).unwrap().done);
+        let _ = env.step(idle());
+    }
+
+    #[test]
+    fn reward_follows_the_weights() {
+        let mut env = Env::new(small_constants(), BlobType::Prey);
+        env.rewards = RewardWeights {
+            energy: 0.0,
+            kill: 0.0,
+            survival: 1.0,
+        };
+        env.reset(2);
+        let result = env.step(idle()).unwrap();
+        assert!(env.agent().is_some(), "the agent survives its first step");
+        assert_eq!(result.reward, 1.0);
+
+        env.rewards = RewardWeights {
+            energy: 1.0,
+            kill: 0.0,
+            survival: 0.0,
+        };
+        env.reset(2);
+        let before = env.agent().unwrap().energy;
+        let result = env.step(idle()).unwrap();
+        let after = env.agent().unwrap().energy;
+        // a prey standing still only gains its base energy
+        assert!((result.reward - (after - before)).abs() < 1e-6);
+        assert!(result.reward > 0.0);
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong outside the simulation itself, with the file or
+/// setting it happened on.
+#[derive(Debug)]
+pub enum Error {
+    // reading or writing a file
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    // a JSON file that doesn't parse or lacks a field; serde names the field and line
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+    // a RON export that doesn't parse or can't be written
+    Ron {
+        path: String,
+        message: String,
+    },
+    // a setting with a value the simulation can't use
+    Config {
+        field: String,
+        message: String,
+    },
+    // drawing a frame of the animation
+    Render {
+        path: String,
+        message: String,
+    },
+    // the external agent crashed or broke the protocol
+    Agent(io::Error),
+    NoCheckpoint {
+        path: String,
+    },
+    NoRun {
+        root: String,
+        seed: i32,
+    },
+    // a re-run that stopped matching the recorded one
+    Diverged {
+        age: i32,
+        detail: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &str, source: io::Error) -> Error {
+        Error::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub fn ron(path: &str, error: impl fmt::Display) -> Error {
+        Error::Ron {
+            path: path.to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn json(path: &str, source: serde_json::Error) -> Error {
+        Error::Json {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{path}: {source}"),
+            Error::Json { path, source } => write!(f, "{path}: {source}"),
+            Error::Ron { path, message } => write!(f, "{path}: {message}"),
+            Error::Config { field, message } => write!(f, "{field}: {message}"),
+            Error::Render { path, message } => write!(f, "couldn't draw {path}: {message}"),
+            Error::Agent(source) => write!(f, "external agent: {source}"),
+            Error::NoCheckpoint { path } => write!(f, "no checkpoint in {path}"),
+            Error::NoRun { root, seed } => write!(f, "no run of seed {seed} in {root}"),
+            Error::Diverged { age, detail } => write!(f, "runs diverge at age {age}: {detail}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Json { source, .. } => Some(source),
+            Error::Agent(source) => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, Write};
+
+use crate::mods::blobs::{Blob, BlobType};
+use crate::mods::phylogeny::Fate;
+
+/// Something that happened to a blob during an update. A parent splitting in
+/// two is logged as its death (cause "reproduced") followed by two births.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Birth {
+        age: i32,
+        id: u64,
+        parent: Option<u64>,
+        blob_type: BlobType,
+        energy: f32,
+        position: (f32, f32),
+    },
+    Death {
+        age: i32,
+        id: u64,
+        blob_type: BlobType,
+        cause: Fate,
+        energy: f32,
+        position: (f32, f32),
+    },
+    // energies are from just before the prey was eaten
+    Kill {
+        age: i32,
+        predator: u64,
+        prey: u64,
+        predator_energy: f32,
+        prey_energy: f32,
+        position: (f32, f32),
+    },
+}
+
+impl Event {
+    pub fn birth(blob: &Blob, age: i32) -> Event {
+        Event::Birth {
+            age,
+            id: blob.id,
+            parent: blob.parent,
+            blob_type: blob.blob_type,
+            energy: blob.energy,
+            position: blob.position,
+        }
+    }
+
+    pub fn death(blob: &Blob, age: i32, cause: Fate) -> Event {
+        Event::Death {
+            age,
+            id: blob.id,
+            blob_type: blob.blob_type,
+            cause,
+            energy: blob.energy,
+            position: blob.position,
+        }
+    }
+}
+
+// One JSON object per line
+#[derive(Debug)]
+pub struct EventWriter {
+    writer: BufWriter<File>,
+}
+
+impl EventWriter {
+    pub fn from_file(file: File) -> Self {
+        Self {
+            writer: BufWriter::new(file),
+        }
+    }
+
+    // bytes written so far, flushing what is buffered
+    pub fn position(&mut self) -> io::Result<u64> {
+        self.writer.stream_position()
+    }
+
+    // dropping flushes too, but loses the error
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn write_events(&mut self, events: &[Event]) -> io::Result<()> {
+        for event in events {
+            serde_json::to_writer(&mut self.writer, event)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+}
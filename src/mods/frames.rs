@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
 
-use crate::mods::blobs::Blob;
+use crate::mods::blobs::{Blob, BlobType};
+use crate::mods::pheromones::PheromoneField;
 
 #[derive(Serialize, Deserialize)]
 pub struct FrameBlob {
@@ -10,12 +11,42 @@ pub struct FrameBlob {
     pub y: f32,
     pub is_prey: bool,
     pub energy: f32,
+    pub body_mass: f32,
+    pub metabolism: f32,
+    pub top_speed: f32,
+    pub turn_rate: f32,
+    pub id: u64,
+    // 0 for founders, ids start at 1
+    pub parent: u64,
+    pub birth_age: i32,
+    pub generation: u32,
+}
+
+impl From<&Blob> for FrameBlob {
+    fn from(blob: &Blob) -> Self {
+        let body = &blob.genome.body;
+        FrameBlob {
+            x: blob.position.0,
+            y: blob.position.1,
+            is_prey: blob.blob_type == BlobType::Prey,
+            energy: blob.energy,
+            body_mass: body.body_mass,
+            metabolism: body.metabolism,
+            top_speed: body.top_speed,
+            turn_rate: body.turn_rate,
+            id: blob.id,
+            parent: blob.parent.unwrap_or(0),
+            birth_age: blob.birth_age,
+            generation: blob.generation,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Frame {
     pub age: usize,
     pub blobs: Vec<FrameBlob>,
+    pub pheromones: Vec<PheromoneField>,
 }
 
 #[derive(Debug)]
@@ -24,15 +55,28 @@ pub struct FrameWriter {
 }
 
 impl FrameWriter {
-    pub fn new(path: &str) -> std::io::Result<Self> {
-        let file = File::create(path)?;
-
-        Ok(Self {
+    pub fn from_file(file: File) -> Self {
+        Self {
             writer: BufWriter::new(file),
-        })
+        }
+    }
+
+    // bytes written so far, flushing what is buffered
+    pub fn position(&mut self) -> io::Result<u64> {
+        self.writer.stream_position()
     }
 
-    pub fn write_frame(&mut self, age: usize, blobs: &[Blob]) -> std::io::Result<()> {
+    // dropping flushes too, but loses the error
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn write_frame(
+        &mut self,
+        age: usize,
+        blobs: &[Blob],
+        pheromones: &[PheromoneField],
+    ) -> std::io::Result<()> {
         self.writer.write_all(&(age as u64).to_le_bytes())?;
 
         self.writer.write_all(&(blobs.len() as u32).to_le_bytes())?;
@@ -45,8 +89,121 @@ impl FrameWriter {
             self.writer.write_all(&[blob_type])?;
 
             self.writer.write_all(&blob.energy.to_le_bytes())?;
+
+            let body = &blob.genome.body;
+            self.writer.write_all(&body.body_mass.to_le_bytes())?;
+            self.writer.write_all(&body.metabolism.to_le_bytes())?;
+            self.writer.write_all(&body.top_speed.to_le_bytes())?;
+            self.writer.write_all(&body.turn_rate.to_le_bytes())?;
+
+            self.writer.write_all(&blob.id.to_le_bytes())?;
+            self.writer
+                .write_all(&blob.parent.unwrap_or(0).to_le_bytes())?;
+            self.writer.write_all(&blob.birth_age.to_le_bytes())?;
+            self.writer.write_all(&blob.generation.to_le_bytes())?;
+        }
+
+        self.writer.write_all(&[pheromones.len() as u8])?;
+        for field in pheromones {
+            self.writer.write_all(&(field.cols as u32).to_le_bytes())?;
+            self.writer.write_all(&(field.rows as u32).to_le_bytes())?;
+            self.writer.write_all(&field.cell_size.0.to_le_bytes())?;
+            self.writer.write_all(&field.cell_size.1.to_le_bytes())?;
+            for value in &field.values {
+                self.writer.write_all(&value.to_le_bytes())?;
+            }
         }
 
         Ok(())
     }
 }
+
+#[derive(Debug)]
+pub struct FrameReader {
+    reader: BufReader<File>,
+}
+
+impl FrameReader {
+    pub fn open(path: &str) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+
+        Ok(Self {
+            reader: BufReader::new(file),
+        })
+    }
+
+    fn read_bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0; N];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    // None at a clean end of file, an error if the file stops mid-frame
+    pub fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        let mut age = [0; 8];
+        let read = self.reader.read(&mut age)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.reader.read_exact(&mut age[read..])?;
+        let age = u64::from_le_bytes(age) as usize;
+
+        let count = u32::from_le_bytes(self.read_bytes()?) as usize;
+        let mut blobs = Vec::with_capacity(count);
+        for _ in 0..count {
+            let x = f32::from_le_bytes(self.read_bytes()?);
+            let y = f32::from_le_bytes(self.read_bytes()?);
+            let [blob_type] = self.read_bytes()?;
+            let energy = f32::from_le_bytes(self.read_bytes()?);
+            blobs.push(FrameBlob {
+                x,
+                y,
+                is_prey: blob_type == BlobType::Prey as u8,
+                energy,
+                body_mass: f32::from_le_bytes(self.read_bytes()?),
+                metabolism: f32::from_le_bytes(self.read_bytes()?),
+                top_speed: f32::from_le_bytes(self.read_bytes()?),
+                turn_rate: f32::from_le_bytes(self.read_bytes()?),
+                id: u64::from_le_bytes(self.read_bytes()?),
+                parent: u64::from_le_bytes(self.read_bytes()?),
+                birth_age: i32::from_le_bytes(self.read_bytes()?),
+                generation: u32::from_le_bytes(self.read_bytes()?),
+            });
+        }
+
+        let [field_count] = self.read_bytes()?;
+        let mut pheromones = Vec::with_capacity(field_count as usize);
+        for _ in 0..field_count {
+            let cols = u32::from_le_bytes(self.read_bytes()?) as usize;
+            let rows = u32::from_le_bytes(self.read_bytes()?) as usize;
+            let cell_size = (
+                f32::from_le_bytes(self.read_bytes()?),
+                f32::from_le_bytes(self.read_bytes()?),
+            );
+            let mut values = Vec::with_capacity(cols * rows);
+            for _ in 0..cols * rows {
+                values.push(f32::from_le_bytes(self.read_bytes()?));
+            }
+            pheromones.push(PheromoneField {
+                cols,
+                rows,
+                cell_size,
+                values,
+            });
+        }
+
+        Ok(Some(Frame {
+            age,
+            blobs,
+            pheromones,
+        }))
+    }
+}
+
+impl Iterator for FrameReader {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
@@ -0,0 +1,227 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+use serde::{Deserialize, Serialize};
+
+use super::brains::{which_plasticity, Brain, Plasticity};
+use super::constants::Constants;
+
+// Sensor layout mutates on its own, much finer, scale than the weights
+const SEPARATION_STEP: f32 = 0.01;
+
+/// Heritable body plan. Every trait has an upkeep (see `World::base_energy`) and
+/// mass also makes moving dearer, so none of them is free to grow.
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Body {
+    // mass that is not stored energy; adds to the radius
+    pub body_mass: f32,
+    // scales the base energy gain (prey) or loss (predators) and how fast the gut empties
+    pub metabolism: f32,
+    pub top_speed: f32,
+    // largest heading change per age, in radians
+    pub turn_rate: f32,
+}
+
+impl Body {
+    // the body every blob had before bodies were heritable
+    pub fn from_constants(constants: &Constants) -> Body {
+        Body {
+            body_mass: 0.0,
+            metabolism: 1.0,
+            top_speed: constants.max_speed,
+            turn_rate: constants.max_angle_diff,
+        }
+    }
+
+    // each trait moves by `rate` times its own reference size (`scale`) and stays bounded
+    fn mutate(&self, mutation: &Mutation, scale: &Body, rng: &mut impl Rng) -> Body {
+        let rate = mutation.body_rate;
+        Body {
+            body_mass: mutation
+                .perturb(self.body_mass, rate * scale.body_mass.max(1.0), rng)
+                .max(0.0),
+            metabolism: mutation
+                .perturb(self.metabolism, rate * scale.metabolism, rng)
+                .max(0.1),
+            top_speed: mutation
+                .perturb(self.top_speed, rate * scale.top_speed, rng)
+                .max(0.0),
+            turn_rate: mutation
+                .perturb(self.turn_rate, rate * scale.turn_rate, rng)
+                .clamp(0.0, std::f32::consts::PI),
+        }
+    }
+}
+
+/// Everything a blob passes on to its children. The brain and body are built from
+/// it at birth, so nothing heritable lives anywhere else.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Genome {
+    pub weights: Vec<Vec<Vec<f32>>>,
+    pub neuron_separation_radians: f32,
+    // the blob's own weight mutation size, only used with self-adaptive mutation
+    pub mutation_step: f32,
+    pub body: Body,
+    // per-connection Hebbian learning coefficients, shaped like the weights;
+    // empty unless brains are plastic
+    pub plasticity: Vec<Vec<Vec<f32>>>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub enum Perturbation {
+    Uniform,
+    Gaussian,
+}
+
+pub fn which_perturbation(constants: &Constants) -> Perturbation {
+    match constants.mutation_operator.as_str() {
+        "gaussian" => Perturbation::Gaussian,
+        _ => Perturbation::Uniform,
+    }
+}
+
+/// How a genome changes from parent to child. Each gene mutates with probability
+/// `gene_probability` by a `rate`-sized perturbation, which is occasionally replaced by
+/// a jump `jump_scale` times bigger; weights are then clamped to `weight_bound` (if > 0).
+/// When `self_adaptive`, weights use the genome's own `mutation_step` instead of `rate`,
+/// after that step has itself been multiplied by exp(`step_tau` * N(0, 1)).
+/// Body traits only mutate when `body_rate` > 0, relative to the `body_scale` sizes.
+/// With `inherit_learned` the weights a brain learned in its lifetime are the ones passed on.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Mutation {
+    pub perturbation: Perturbation,
+    pub rate: f32,
+    pub gene_probability: f32,
+    pub jump_probability: f32,
+    pub jump_scale: f32,
+    pub weight_bound: f32,
+    pub self_adaptive: bool,
+    pub step_tau: f32,
+    pub min_step: f32,
+    pub body_rate: f32,
+    pub body_scale: Body,
+    pub inherit_learned: bool,
+}
+
+impl Mutation {
+    pub fn from_constants(constants: &Constants) -> Mutation {
+        Mutation {
+            perturbation: which_perturbation(constants),
+            rate: constants.mutation_rate,
+            gene_probability: constants.gene_mutation_probability,
+            jump_probability: constants.large_mutation_probability,
+            jump_scale: constants.large_mutation_scale,
+            weight_bound: constants.weight_bound,
+            self_adaptive: constants.self_adaptive_mutation,
+            step_tau: constants.mutation_step_tau,
+            min_step: constants.min_mutation_step,
+            body_rate: if constants.heritable_body {
+                constants.body_mutation_rate
+            } else {
+                0.0
+            },
+            body_scale: Body::from_constants(constants),
+            inherit_learned: matches!(which_plasticity(constants), Plasticity::Lamarckian),
+        }
+    }
+
+    // coins are only tossed for features that are switched on, so the default
+    // configuration draws exactly one number per gene
+    fn perturb(&self, value: f32, scale: f32, rng: &mut impl Rng) -> f32 {
+        if self.gene_probability < 1.0 && rng.gen::<f32>() >= self.gene_probability {
+            return value;
+        }
+        let scale = if self.jump_probability > 0.0 && rng.gen::<f32>() < self.jump_probability {
+            scale * self.jump_scale
+        } else {
+            scale
+        };
+        let noise: f32 = match self.perturbation {
+            Perturbation::Uniform => rng.gen_range(-1.0..1.0),
+            Perturbation::Gaussian => rng.sample(StandardNormal),
+        };
+        value + scale * noise
+    }
+
+    fn bound_weight(&self, weight: f32) -> f32 {
+        if self.weight_bound > 0.0 {
+            weight.clamp(-self.weight_bound, self.weight_bound)
+        } else {
+            weight
+        }
+    }
+
+    fn mutate_matrices(
+        &self,
+        matrices: &[Vec<Vec<f32>>],
+        rate: f32,
+        rng: &mut impl Rng,
+    ) -> Vec<Vec<Vec<f32>>> {
+        matrices
+            .iter()
+            .map(|matrix| {
+                matrix
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .map(|&w| self.bound_weight(self.perturb(w, rate, rng)))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Genome {
+    pub fn random(
+        network_shape: &[i32],
+        neuron_separation_radians: f32,
+        mutation_step: f32,
+        body: Body,
+        plastic: bool,
+        rng: &mut impl Rng,
+    ) -> Genome {
+        let weights = Brain::init_random(network_shape, rng);
+        let plasticity = if plastic {
+            Brain::init_random(network_shape, rng)
+        } else {
+            Vec::new()
+        };
+        Genome {
+            weights,
+            neuron_separation_radians,
+            mutation_step,
+            body,
+            plasticity,
+        }
+    }
+
+    pub fn mutate(&self, mutation: &Mutation, rng: &mut impl Rng) -> Genome {
+        // log-normal, so the step stays positive and halving is as likely as doubling
+        let (mutation_step, rate) = if mutation.self_adaptive {
+            let noise: f32 = rng.sample(StandardNormal);
+            let step =
+                (self.mutation_step * (mutation.step_tau * noise).exp()).max(mutation.min_step);
+            (step, step)
+        } else {
+            (self.mutation_step, mutation.rate)
+        };
+        let weights = mutation.mutate_matrices(&self.weights, rate, rng);
+        let neuron_separation_radians = mutation
+            .perturb(self.neuron_separation_radians, SEPARATION_STEP, rng)
+            .clamp(0.0, std::f32::consts::TAU);
+        let body = if mutation.body_rate > 0.0 {
+            self.body.mutate(mutation, &mutation.body_scale, rng)
+        } else {
+            self.body.clone()
+        };
+        let plasticity = mutation.mutate_matrices(&self.plasticity, rate, rng);
+        Genome {
+            weights,
+            neuron_separation_radians,
+            mutation_step,
+            body,
+            plasticity,
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs;
+
+use crate::mods::errors::{Error, Result};
+use crate::mods::world::World;
+
+// the file in a run directory the hashes go to, one "age,hash" line per age
+pub const FILE: &str = "hashes.csv";
+pub const HEADER: &str = "age,hash";
+
+// FNV-1a, 64 bit: its output is fixed by its definition, unlike std's hashers,
+// so a hash means the same thing across builds, versions and platforms
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // bit for bit, so -0.0 and 0.0 or two NaNs differ when the runs did
+    fn f32(&mut self, value: f32) {
+        self.bytes(&value.to_bits().to_le_bytes());
+    }
+
+    // with the length first, so values can't shift from one list into the next
+    fn f32s(&mut self, values: &[f32]) {
+        self.bytes(&(values.len() as u64).to_le_bytes());
+        for value in values {
+            self.f32(*value);
+        }
+    }
+
+    fn matrices(&mut self, matrices: &[Vec<Vec<f32>>]) {
+        self.bytes(&(matrices.len() as u64).to_le_bytes());
+        for matrix in matrices {
+            self.bytes(&(matrix.len() as u64).to_le_bytes());
+            for row in matrix {
+                self.f32s(row);
+            }
+        }
+    }
+}
+
+/// A hash of everything that sets where a run goes next: every blob's id, type,
+/// position, heading, energy, handling, satiety and signal, its brain weights and
+/// whole genome, in order, then the pheromone fields and the next id. Two runs that
+/// are still identical hash the same at every age.
+pub fn state_hash(world: &World) -> u64 {
+    let mut hash = Fnv::new();
+    hash.bytes(&(world.blobs.len() as u64).to_le_bytes());
+    for blob in &world.blobs {
+        hash.bytes(&blob.id.to_le_bytes());
+        hash.bytes(&[blob.blob_type as u8]);
+        hash.f32(blob.position.0);
+        hash.f32(blob.position.1);
+        hash.f32(blob.angle);
+        hash.f32(blob.energy);
+        hash.bytes(&blob.handling.to_le_bytes());
+        hash.f32(blob.satiety);
+        hash.f32s(&blob.signal);
+        hash.matrices(&blob.brain.weights);
+
+        let genome = &blob.genome;
+        hash.matrices(&genome.weights);
+        hash.f32(genome.neuron_separation_radians);
+        hash.f32(genome.mutation_step);
+        let body = &genome.body;
+        hash.f32s(&[
+            body.body_mass,
+            body.metabolism,
+            body.top_speed,
+            body.turn_rate,
+        ]);
+        hash.matrices(&genome.plasticity);
+    }
+    for field in &world.pheromones {
+        hash.f32s(&field.values);
+    }
+    hash.bytes(&world.next_id.to_le_bytes());
+    hash.0
+}
+
+pub fn row(age: i32, hash: u64) -> String {
+    format!("{age},{hash:016x}")
+}
+
+// (age, hash) of every line of a hashes file
+pub fn read(path: &str) -> Result<Vec<(i32, u64)>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let invalid = |line: &str| Error::Config {
+        field: path.to_string(),
+        message: format!("{line:?} is not an age and a hash"),
+    };
+    text.lines()
+        .skip(1)
+        .map(|line| {
+            let (age, hash) = line.split_once(',').ok_or_else(|| invalid(line))?;
+            Ok((
+                age.parse().map_err(|_| invalid(line))?,
+                u64::from_str_radix(hash, 16).map_err(|_| invalid(line))?,
+            ))
+        })
+        .collect()
+}
+
+/// The first age at which two hash records disagree, including one of them
+/// stopping before the other, or None if they match throughout.
+pub fn first_divergence(recorded: &[(i32, u64)], rerun: &[(i32, u64)]) -> Option<(i32, String)> {
+    for (recorded, rerun) in recorded.iter().zip(rerun) {
+        if recorded != rerun {
+            let detail = format!(
+                "recorded age {} hash {:016x}, re-run age {} hash {:016x}",
+                recorded.0, recorded.1, rerun.0, rerun.1
+            );
+            return Some((recorded.0.min(rerun.0), detail));
+        }
+    }
+    let (longer, which) = match recorded.len().cmp(&rerun.len()) {
+        std::cmp::Ordering::Equal => return None,
+        std::cmp::Ordering::Greater => (recorded, "re-run"),
+        std::cmp::Ordering::Less => (rerun, "recorded run"),
+    };
+    let (age, _) = longer[recorded.len().min(rerun.len())];
+    Some((age, format!("the {which} had already stopped")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORDED: [(i32, u64); 3] = [(0, 0xa), (1, 0xb), (2, 0xc)];
+
+    #[test]
+    fn identical_records_dont_diverge() {
+        assert_eq!(first_divergence(&RECORDED, &RECORDED), None);
+    }
+
+    #[test]
+    fn divergence_is_the_first_differing_age() {
+        let rerun = [(0, 0xa), (1, 0xbb), (2, 0xcc)];
+        let (age, detail) = first_divergence(&RECORDED, &rerun).unwrap();
+        assert_eq!(age, 1);
+        assert!(detail.contains("00000000000000bb"), "{detail}");
+    }
+
+    #[test]
+    fn stopping_early_is_a_divergence() {
+        let (age, detail) = first_divergence(&RECORDED, &RECORDED[..2]).unwrap();
+        assert_eq!(age, 2);
+        assert!(detail.contains("re-run had already stopped"), "{detail}");
+        let (age, detail) = first_divergence(&RECORDED[..1], &RECORDED).unwrap();
+        assert_eq!(age, 1);
+        assert!(
+            detail.contains("recorded run had already stopped"),
+            "{detail}"
+        );
+    }
+
+    #[test]
+    fn rows_read_back() {
+        let path =
+            std::env::temp_dir().join(format!("blobworld-hashes-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let rows: Vec<String> = RECORDED.iter().map(|&(age, hash)| row(age, hash)).collect();
+        fs::write(&path, format!("{HEADER}\n{}\n", rows.join("\n"))).unwrap();
+        let read_back = read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read_back.unwrap(), RECORDED);
+    }
+}
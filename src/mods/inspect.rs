@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::mods::blobs::{Blob, BlobType};
+use crate::mods::stats::{Demography, Stats};
+use crate::mods::world::World;
+
+// min, mean, standard deviation and max of some values
+struct Spread {
+    min: f32,
+    mean: f32,
+    std: f32,
+    max: f32,
+}
+
+impl Spread {
+    fn of(values: impl Iterator<Item = f32>) -> Option<Spread> {
+        let values: Vec<f32> = values.collect();
+        if values.is_empty() {
+            return None;
+        }
+        let n = values.len() as f32;
+        let mean = values.iter().sum::<f32>() / n;
+        let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        Some(Spread {
+            min: values.iter().cloned().fold(f32::INFINITY, f32::min),
+            mean,
+            std: var.sqrt(),
+            max: values.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+        })
+    }
+}
+
+impl std::fmt::Display for Spread {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.4} .. {:.4}, mean {:.4} ± {:.4}",
+            self.min, self.max, self.mean, self.std
+        )
+    }
+}
+
+fn species_summary(out: &mut String, blobs: &[&Blob]) {
+    let line = |label: &str, spread: Option<Spread>| match spread {
+        Some(spread) => format!("  {label:<20}{spread}\n"),
+        None => String::new(),
+    };
+    out.push_str(&line(
+        "weights",
+        Spread::of(
+            blobs
+                .iter()
+                .flat_map(|b| b.brain.weights.iter().flatten().flatten().cloned()),
+        ),
+    ));
+    out.push_str(&line(
+        "neuron separation",
+        Spread::of(blobs.iter().map(|b| b.genome.neuron_separation_radians)),
+    ));
+    let max_generation = blobs.iter().map(|b| b.generation).max().unwrap_or(0);
+    if max_generation > 0 {
+        out.push_str(&line(
+            "generation",
+            Spread::of(blobs.iter().map(|b| b.generation as f32)),
+        ));
+    }
+}
+
+/// A readable summary of a world: its constants, population, energies, brains and
+/// lineage depth. Nothing is simulated.
+pub fn summary(world: &World) -> String {
+    let mut out = String::new();
+
+    out.push_str("constants\n");
+    for (key, value) in world.constants.fields() {
+        let _ = writeln!(out, "  {key:<28}{value}");
+    }
+
+    let stats = Stats::collect(world, 0, &[Demography::default(), Demography::default()]);
+    out.push_str("\npopulation\n");
+    let _ = writeln!(
+        out,
+        "  {} blobs: {} prey, {} predators, next id {}",
+        world.blobs.len(),
+        stats.prey.count,
+        stats.predators.count,
+        world.next_id
+    );
+
+    let mut shapes: BTreeMap<&[i32], usize> = BTreeMap::new();
+    for blob in &world.blobs {
+        *shapes.entry(&blob.brain.network_shape).or_default() += 1;
+    }
+    out.push_str("\nbrain shapes\n");
+    for (shape, count) in shapes {
+        let _ = writeln!(out, "  {shape:?}: {count} blobs");
+    }
+
+    for (blob_type, name, species) in [
+        (BlobType::Prey, "prey", &stats.prey),
+        (BlobType::Predator, "predators", &stats.predators),
+    ] {
+        let blobs: Vec<&Blob> = world
+            .blobs
+            .iter()
+            .filter(|b| b.blob_type == blob_type)
+            .collect();
+        let _ = writeln!(out, "\n{name}");
+        if blobs.is_empty() {
+            out.push_str("  none\n");
+            continue;
+        }
+        let _ = writeln!(
+            out,
+            "  {:<20}{:.4} .. {:.4}, median {:.4}, mean {:.4}",
+            "energy",
+            species.min_energy,
+            species.max_energy,
+            species.median_energy,
+            species.mean_energy
+        );
+        species_summary(&mut out, &blobs);
+    }
+
+    if !world.phylogeny.lineages.is_empty() {
+        let _ = writeln!(
+            out,
+            "\nphylogeny\n  {} blobs recorded",
+            world.phylogeny.lineages.len()
+        );
+    }
+    out
+}
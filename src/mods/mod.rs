@@ -1,8 +1,24 @@
 pub mod activations;
+pub mod agents;
 pub mod blobs;
 pub mod brains;
+pub mod checkpoints;
 pub mod cli;
 pub mod constants;
+pub mod env;
+pub mod errors;
+pub mod events;
 pub mod frames;
+pub mod genomes;
+pub mod hashes;
+pub mod inspect;
+pub mod pheromones;
+pub mod phylogeny;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod render;
+pub mod runs;
+pub mod saves;
+pub mod stats;
 pub mod utils;
 pub mod world;
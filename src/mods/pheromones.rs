@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+use crate::mods::utils::cap;
+
+/// A scalar scent field on a regular grid covering the (wrapping) world.
+/// Values are stored row-major, `rows` cells along y by `cols` cells along x.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct PheromoneField {
+    pub cols: usize,
+    pub rows: usize,
+    pub cell_size: (f32, f32),
+    pub values: Vec<f32>,
+}
+
+impl PheromoneField {
+    pub fn new(shape: (f32, f32), cell_size: f32) -> PheromoneField {
+        let cols = ((shape.0 / cell_size).ceil() as usize).max(1);
+        let rows = ((shape.1 / cell_size).ceil() as usize).max(1);
+        PheromoneField {
+            cols,
+            rows,
+            cell_size: (shape.0 / cols as f32, shape.1 / rows as f32),
+            values: vec![0.0; cols * rows],
+        }
+    }
+
+    fn index(&self, col: isize, row: isize) -> usize {
+        let col = col.rem_euclid(self.cols as isize) as usize;
+        let row = row.rem_euclid(self.rows as isize) as usize;
+        row * self.cols + col
+    }
+
+    fn cell(&self, position: (f32, f32)) -> (isize, isize) {
+        let x = cap(position.0, self.cols as f32 * self.cell_size.0);
+        let y = cap(position.1, self.rows as f32 * self.cell_size.1);
+        (
+            (x / self.cell_size.0) as isize,
+            (y / self.cell_size.1) as isize,
+        )
+    }
+
+    pub fn get(&self, col: isize, row: isize) -> f32 {
+        self.values[self.index(col, row)]
+    }
+
+    pub fn deposit(&mut self, position: (f32, f32), amount: f32) {
+        let (col, row) = self.cell(position);
+        let idx = self.index(col, row);
+        self.values[idx] += amount;
+    }
+
+    // central differences around the cell containing `position`, in value per world unit
+    pub fn gradient(&self, position: (f32, f32)) -> (f32, f32) {
+        let (col, row) = self.cell(position);
+        (
+            (self.get(col + 1, row) - self.get(col - 1, row)) / (2.0 * self.cell_size.0),
+            (self.get(col, row + 1) - self.get(col, row - 1)) / (2.0 * self.cell_size.1),
+        )
+    }
+
+    /// One explicit diffusion step on the wrapping grid followed by evaporation.
+    /// `diffusion` is the fraction exchanged with each of the four neighbours and
+    /// must stay at or below 0.25 for the step to be stable.
+    pub fn step(&mut self, diffusion: f32, decay: f32) {
+        let mut next = vec![0.0; self.values.len()];
+        for row in 0..self.rows as isize {
+            for col in 0..self.cols as isize {
+                let value = self.get(col, row);
+                let neighbours = self.get(col - 1, row)
+                    + self.get(col + 1, row)
+                    + self.get(col, row - 1)
+                    + self.get(col, row + 1);
+                next[self.index(col, row)] =
+                    (value + diffusion * (neighbours - 4.0 * value)) * (1.0 - decay);
+            }
+        }
+        self.values = next;
+    }
+
+    pub fn max(&self) -> f32 {
+        self.values.iter().cloned().fold(0.0, f32::max)
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::mods::blobs::{Blob, BlobType};
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Fate {
+    Alive,
+    // split into two children, so the lineage goes on
+    Reproduced,
+    Killed,
+    Starved,
+}
+
+impl Fate {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fate::Alive => "alive",
+            Fate::Reproduced => "reproduced",
+            Fate::Killed => "killed",
+            Fate::Starved => "starved",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Lineage {
+    pub parent: Option<u64>,
+    pub blob_type: BlobType,
+    pub birth_age: i32,
+    pub death_age: Option<i32>,
+    pub fate: Fate,
+}
+
+/// Every blob that ever lived, keyed by id. Children always have bigger ids
+/// than their parent, so walking the ids backwards visits children first.
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Phylogeny {
+    pub lineages: BTreeMap<u64, Lineage>,
+}
+
+impl Phylogeny {
+    pub fn born(&mut self, blob: &Blob) {
+        self.lineages.insert(
+            blob.id,
+            Lineage {
+                parent: blob.parent,
+                blob_type: blob.blob_type,
+                birth_age: blob.birth_age,
+                death_age: None,
+                fate: Fate::Alive,
+            },
+        );
+    }
+
+    pub fn died(&mut self, id: u64, age: i32, fate: Fate) {
+        if let Some(lineage) = self.lineages.get_mut(&id) {
+            lineage.death_age = Some(age);
+            lineage.fate = fate;
+        }
+    }
+
+    // the living blobs and all their ancestors
+    fn survivors_and_ancestors(&self) -> HashSet<u64> {
+        let mut kept = HashSet::new();
+        for (&id, lineage) in &self.lineages {
+            if lineage.fate != Fate::Alive {
+                continue;
+            }
+            let mut next = Some(id);
+            while let Some(id) = next {
+                if !kept.insert(id) {
+                    break;
+                }
+                next = self.lineages.get(&id).and_then(|l| l.parent);
+            }
+        }
+        kept
+    }
+
+    fn kept(&self, prune: bool) -> Vec<(&u64, &Lineage)> {
+        let survivors = prune.then(|| self.survivors_and_ancestors());
+        self.lineages
+            .iter()
+            .filter(|(id, _)| survivors.as_ref().is_none_or(|kept| kept.contains(id)))
+            .collect()
+    }
+
+    /// One row per blob: id, parent (empty for founders), type, birth and death
+    /// age (empty while alive) and fate.
+    pub fn write_csv(&self, path: &str, prune: bool) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "id,parent,type,birth_age,death_age,cause")?;
+        for (id, lineage) in self.kept(prune) {
+            let blob_type = match lineage.blob_type {
+                BlobType::Prey => "prey",
+                BlobType::Predator => "predator",
+            };
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                id,
+                lineage.parent.map(|p| p.to_string()).unwrap_or_default(),
+                blob_type,
+                lineage.birth_age,
+                lineage.death_age.map(|a| a.to_string()).unwrap_or_default(),
+                lineage.fate.name()
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// The ancestry as a Newick tree labelled by id, with branch lengths in ages
+    /// (blobs still alive are cut at `end_age`). The founders hang from an unnamed
+    /// root. When pruned, ancestors left with a single child are merged into it.
+    pub fn newick(&self, end_age: i32, prune: bool) -> String {
+        let kept = self.kept(prune);
+        let mut children: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (&id, lineage) in &kept {
+            if let Some(parent) = lineage.parent {
+                children.entry(parent).or_default().push(id);
+            }
+        }
+        // subtree text without its branch length, and that length
+        let mut subtrees: HashMap<u64, (String, i32)> = HashMap::new();
+        for &(&id, lineage) in kept.iter().rev() {
+            let length = lineage.death_age.unwrap_or(end_age) - lineage.birth_age;
+            let mut kids: Vec<(String, i32)> = children
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .filter_map(|child| subtrees.remove(child))
+                .collect();
+            let subtree = match kids.len() {
+                0 => (id.to_string(), length),
+                1 if prune => {
+                    let (text, child_length) = kids.remove(0);
+                    (text, length + child_length)
+                }
+                _ => {
+                    let kids: Vec<String> = kids
+                        .iter()
+                        .map(|(text, length)| format!("{text}:{length}"))
+                        .collect();
+                    (format!("({}){}", kids.join(","), id), length)
+                }
+            };
+            subtrees.insert(id, subtree);
+        }
+        let roots: Vec<String> = kept
+            .iter()
+            .filter_map(|(id, _)| subtrees.remove(id))
+            .map(|(text, length)| format!("{text}:{length}"))
+            .collect();
+        format!("({});", roots.join(","))
+    }
+
+    pub fn write_newick(&self, path: &str, end_age: i32, prune: bool) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", self.newick(end_age, prune))
+    }
+}
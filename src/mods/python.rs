@@ -0,0 +1,193 @@
+use numpy::{IntoPyArray, PyArrayMethods};
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::mods::{
+    constants::Constants,
+    errors::Error,
+    frames::{FrameBlob, FrameReader},
+    pheromones::PheromoneField,
+    world::World,
+};
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> PyErr {
+        match error {
+            Error::Io { .. } => PyIOError::new_err(error.to_string()),
+            Error::Json { .. } | Error::Ron { .. } | Error::Config { .. } => {
+                PyValueError::new_err(error.to_string())
+            }
+            _ => PyRuntimeError::new_err(error.to_string()),
+        }
+    }
+}
+
+// Constants cross the boundary as JSON so the dict keys match constants.json exactly
+fn constants_from_dict(py: Python<'_>, dict: &Bound<'_, PyDict>) -> PyResult<Constants> {
+    let json: String = py
+        .import("json")?
+        .call_method1("dumps", (dict,))?
+        .extract()?;
+    let constants: Constants =
+        serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    // ranges the simulation can't run with are refused here as from a file
+    constants.validate()?;
+    Ok(constants)
+}
+
+fn constants_to_dict<'py>(py: Python<'py>, constants: &Constants) -> PyResult<Bound<'py, PyAny>> {
+    let json =
+        serde_json::to_string(constants).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    py.import("json")?.call_method1("loads", (json,))
+}
+
+// Same field names as the dtype in read_frames.py
+fn blob_arrays<'py>(py: Python<'py>, blobs: &[FrameBlob]) -> PyResult<Bound<'py, PyDict>> {
+    let column = |f: fn(&FrameBlob) -> f32| blobs.iter().map(f).collect::<Vec<f32>>();
+    let dict = PyDict::new(py);
+    dict.set_item("x", column(|b| b.x).into_pyarray(py))?;
+    dict.set_item("y", column(|b| b.y).into_pyarray(py))?;
+    let blob_type: Vec<u8> = blobs.iter().map(|b| u8::from(!b.is_prey)).collect();
+    dict.set_item("type", blob_type.into_pyarray(py))?;
+    dict.set_item("energy", column(|b| b.energy).into_pyarray(py))?;
+    dict.set_item("body_mass", column(|b| b.body_mass).into_pyarray(py))?;
+    dict.set_item("metabolism", column(|b| b.metabolism).into_pyarray(py))?;
+    dict.set_item("top_speed", column(|b| b.top_speed).into_pyarray(py))?;
+    dict.set_item("turn_rate", column(|b| b.turn_rate).into_pyarray(py))?;
+    let ids: Vec<u64> = blobs.iter().map(|b| b.id).collect();
+    dict.set_item("id", ids.into_pyarray(py))?;
+    let parents: Vec<u64> = blobs.iter().map(|b| b.parent).collect();
+    dict.set_item("parent", parents.into_pyarray(py))?;
+    let birth_ages: Vec<i32> = blobs.iter().map(|b| b.birth_age).collect();
+    dict.set_item("birth_age", birth_ages.into_pyarray(py))?;
+    let generations: Vec<u32> = blobs.iter().map(|b| b.generation).collect();
+    dict.set_item("generation", generations.into_pyarray(py))?;
+    Ok(dict)
+}
+
+// Each field as a (rows, cols) array, row 0 at y = 0
+fn field_arrays<'py>(py: Python<'py>, fields: &[PheromoneField]) -> PyResult<Bound<'py, PyList>> {
+    let grids = PyList::empty(py);
+    for field in fields {
+        let grid = field
+            .values
+            .clone()
+            .into_pyarray(py)
+            .reshape([field.rows, field.cols])?;
+        grids.append(grid)?;
+    }
+    Ok(grids)
+}
+
+/// A simulation running in-process, advanced one age at a time.
+#[pyclass(name = "World")]
+pub struct PyWorld {
+    world: World,
+    rng: StdRng,
+    age: i32,
+}
+
+#[pymethods]
+impl PyWorld {
+    #[new]
+    #[pyo3(signature = (constants, seed=None))]
+    fn new(py: Python<'_>, constants: &Bound<'_, PyDict>, seed: Option<u64>) -> PyResult<Self> {
+        let mut constants = constants_from_dict(py, constants)?;
+        if let Some(seed) = seed {
+            constants.seed = seed as i32;
+        }
+        let mut rng = StdRng::seed_from_u64(constants.seed as u64);
+        let world = World::generate(constants, &mut rng);
+        Ok(PyWorld { world, rng, age: 0 })
+    }
+
+    /// Loads a world saved by the CLI; the RNG is reseeded from its constants.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        let world = World::load_from_file(path)?;
+        let rng = StdRng::seed_from_u64(world.constants.seed as u64);
+        Ok(PyWorld { world, rng, age: 0 })
+    }
+
+    fn save(&self, path: &str) -> PyResult<()> {
+        Ok(self.world.save_to_file(path)?)
+    }
+
+    /// Advances the world by `ages` ages (one by default).
+    #[pyo3(signature = (ages=1))]
+    fn update(&mut self, py: Python<'_>, ages: i32) -> PyResult<()> {
+        let PyWorld { world, rng, age } = self;
+        py.detach(|| {
+            for _ in 0..ages {
+                world.update(*age, rng, None)?;
+                *age += 1;
+            }
+            Ok::<_, Error>(())
+        })?;
+        Ok(())
+    }
+
+    #[getter]
+    fn age(&self) -> i32 {
+        self.age
+    }
+
+    #[getter]
+    fn constants<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        constants_to_dict(py, &self.world.constants)
+    }
+
+    fn __len__(&self) -> usize {
+        self.world.blobs.len()
+    }
+
+    /// The current population as a dict of numpy arrays, with the same columns as a
+    /// frame plus the heading angle.
+    fn blobs<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let blobs = &self.world.blobs;
+        let frame_blobs: Vec<FrameBlob> = blobs.iter().map(FrameBlob::from).collect();
+        let dict = blob_arrays(py, &frame_blobs)?;
+        let angles: Vec<f32> = blobs.iter().map(|b| b.angle).collect();
+        dict.set_item("angle", angles.into_pyarray(py))?;
+        Ok(dict)
+    }
+
+    /// The prey and predator pheromone grids, empty when pheromones are off.
+    fn pheromones<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        field_arrays(py, &self.world.pheromones)
+    }
+}
+
+/// Reads a frames .bin file into a list of (age, dict of numpy arrays), or of
+/// (age, arrays, pheromone grids) with `with_fields=True`, like read_frames.py.
+#[pyfunction]
+#[pyo3(signature = (path, with_fields=false))]
+fn read_frames<'py>(
+    py: Python<'py>,
+    path: &str,
+    with_fields: bool,
+) -> PyResult<Bound<'py, PyList>> {
+    let reader = FrameReader::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let frames = PyList::empty(py);
+    for frame in reader {
+        let frame = frame.map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let arrays = blob_arrays(py, &frame.blobs)?;
+        if with_fields {
+            let fields = field_arrays(py, &frame.pheromones)?;
+            frames.append((frame.age, arrays, fields))?;
+        } else {
+            frames.append((frame.age, arrays))?;
+        }
+    }
+    Ok(frames)
+}
+
+#[pymodule]
+fn evolution(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyWorld>()?;
+    m.add_function(wrap_pyfunction!(read_frames, m)?)?;
+    Ok(())
+}
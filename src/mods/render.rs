@@ -0,0 +1,113 @@
+use plotters::coord::types::RangedCoordf32;
+use plotters::prelude::*;
+use std::fs;
+
+use crate::mods::constants::Constants;
+use crate::mods::errors::{Error, Result};
+use crate::mods::frames::{Frame, FrameReader};
+use crate::mods::pheromones::PheromoneField;
+
+pub const SCREEN_SHAPE: (u32, u32) = (1366, 768);
+
+pub type WorldChart<'a, DB> = ChartContext<'a, DB, Cartesian2d<RangedCoordf32, RangedCoordf32>>;
+
+pub fn blob_style(is_prey: bool) -> ShapeStyle {
+    if is_prey {
+        GREEN.mix(0.9).filled()
+    } else {
+        RED.mix(0.9).filled()
+    }
+}
+
+// pixels per world unit, blobs are drawn at radius times this
+pub fn scale(shape: (f32, f32)) -> f32 {
+    (SCREEN_SHAPE.0 as f32 / shape.0).round()
+}
+
+// each field shaded in its own colour, relative to its own maximum
+pub fn draw_pheromones<DB: DrawingBackend>(
+    chart: &mut WorldChart<'_, DB>,
+    fields: &[PheromoneField],
+) -> std::result::Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    for (field, color) in fields.iter().zip([GREEN, RED]) {
+        let max = field.max();
+        if max <= 0.0 {
+            continue;
+        }
+        let (w, h) = field.cell_size;
+        chart.draw_series((0..field.rows).flat_map(|row| {
+            (0..field.cols).map(move |col| {
+                let value = field.get(col as isize, row as isize);
+                let (x, y) = (col as f32 * w, row as f32 * h);
+                Rectangle::new(
+                    [(x, y), (x + w, y + h)],
+                    color.mix(0.5 * (value / max) as f64).filled(),
+                )
+            })
+        }))?;
+    }
+    Ok(())
+}
+
+// A recorded frame drawn the way the live run draws it, minus the neuron rays
+// frames don't keep the headings for
+fn draw_frame(
+    filename: &str,
+    shape: (f32, f32),
+    frame: &Frame,
+    pheromones: bool,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let drawing_area = BitMapBackend::new(filename, SCREEN_SHAPE).into_drawing_area();
+    drawing_area.fill(&WHITE)?;
+    let mut chart =
+        ChartBuilder::on(&drawing_area).build_cartesian_2d(-0.0_f32..shape.0, 0.0_f32..shape.1)?;
+    chart.configure_mesh().draw()?;
+
+    if pheromones {
+        draw_pheromones(&mut chart, &frame.pheromones)?;
+    }
+    chart.draw_series(frame.blobs.iter().map(|blob| {
+        Circle::new(
+            (blob.x, blob.y),
+            (blob.body_mass + blob.energy).sqrt() * scale(shape),
+            blob_style(blob.is_prey),
+        )
+    }))?;
+    drawing_area.present()?;
+    Ok(())
+}
+
+/// Draws every `every`th frame of a frames file into `out_dir`, numbered like the
+/// live animation. The world shape and whether to shade pheromones come from the
+/// constants, frames don't record them. Returns how many images were written.
+pub fn render_frames(
+    frames_path: &str,
+    out_dir: &str,
+    every: usize,
+    constants: &Constants,
+) -> Result<usize> {
+    let every = every.max(1);
+    fs::create_dir_all(out_dir).map_err(|e| Error::io(out_dir, e))?;
+    let reader = FrameReader::open(frames_path).map_err(|e| Error::io(frames_path, e))?;
+    let mut written = 0;
+    for frame in reader {
+        let frame = frame.map_err(|e| Error::io(frames_path, e))?;
+        if !frame.age.is_multiple_of(every) {
+            continue;
+        }
+        let filename = format!("{out_dir}/frame{:04}.png", frame.age / every);
+        println!("{filename}");
+        draw_frame(
+            &filename,
+            constants.world_shape,
+            &frame,
+            constants.graph_pheromones,
+        )
+        .map_err(|e| Error::Render {
+            path: filename.clone(),
+            message: e.to_string(),
+        })?;
+        written += 1;
+    }
+    Ok(written)
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mods::errors::{Error, Result};
+
+pub const DEFAULT_ROOT: &str = "runs";
+const MANIFEST: &str = "manifest.json";
+
+/// Why a run stopped, or `Running` while it hasn't.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitReason {
+    Running,
+    // every age in the constants was simulated
+    Completed,
+    // one of the species died out
+    Extinct,
+    Error,
+}
+
+// How a run that didn't fail came to an end
+#[derive(Clone, Copy, Debug)]
+pub struct RunEnd {
+    pub reason: ExitReason,
+    pub last_age: i32,
+}
+
+/// What a run directory holds besides the outputs: which build made it, with which
+/// seed, when, and how it ended.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub crate_version: String,
+    pub seed: i32,
+    // the save the world was loaded from, if it wasn't generated
+    #[serde(default)]
+    pub loaded_from: Option<String>,
+    pub started: String,
+    pub finished: Option<String>,
+    pub exit_reason: ExitReason,
+    pub error: Option<String>,
+    pub last_age: Option<i32>,
+    // when the run was carried on from a checkpoint
+    pub resumed: Vec<String>,
+}
+
+/// The directory one run writes all its outputs into: the stats CSV, frames, events,
+/// phylogeny, agent log, checkpoints, the effective constants, a copy of the save
+/// it was loaded from and the manifest.
+#[derive(Clone, Debug)]
+pub struct RunDir {
+    pub path: String,
+}
+
+impl RunDir {
+    /// Makes a new directory under `root`, called `name` or, by default, after the
+    /// time and seed. A name that is already taken is an error rather than an
+    /// overwrite; timestamps that collide get a counter.
+    pub fn create(
+        root: &str,
+        name: Option<&str>,
+        seed: i32,
+        loaded_from: Option<&str>,
+    ) -> Result<RunDir> {
+        fs::create_dir_all(root).map_err(|e| Error::io(root, e))?;
+        let path = match name {
+            Some(name) => {
+                let path = format!("{root}/{name}");
+                if Path::new(&path).exists() {
+                    return Err(Error::Config {
+                        field: "name".to_string(),
+                        message: format!("{path} already exists"),
+                    });
+                }
+                path
+            }
+            None => {
+                let base = format!("{root}/{}-seed{seed}", timestamp(now(), "", "-", ""));
+                let mut path = base.clone();
+                let mut count = 1;
+                while Path::new(&path).exists() {
+                    count += 1;
+                    path = format!("{base}-{count}");
+                }
+                path
+            }
+        };
+        fs::create_dir(&path).map_err(|e| Error::io(&path, e))?;
+        let run = RunDir { path };
+        run.write_manifest(&Manifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed,
+            loaded_from: loaded_from.map(str::to_string),
+            started: iso_now(),
+            finished: None,
+            exit_reason: ExitReason::Running,
+            error: None,
+            last_age: None,
+            resumed: Vec::new(),
+        })?;
+        Ok(run)
+    }
+
+    // an existing run directory, which has to have a manifest
+    pub fn open(path: &str) -> Result<RunDir> {
+        let run = RunDir {
+            path: path.trim_end_matches('/').to_string(),
+        };
+        run.manifest()?;
+        Ok(run)
+    }
+
+    // the run a checkpoint file was written by, <run>/checkpoints/<age>.ckpt
+    pub fn of_checkpoint(checkpoint: &str) -> Result<RunDir> {
+        let run = Path::new(checkpoint)
+            .parent()
+            .and_then(Path::parent)
+            .map(|path| path.to_string_lossy().to_string())
+            .filter(|path| !path.is_empty())
+            .unwrap_or_else(|| ".".to_string());
+        RunDir::open(&run)
+    }
+
+    /// The run of the seed under `root` that started last.
+    pub fn latest(root: &str, seed: i32) -> Result<RunDir> {
+        let mut latest: Option<(String, RunDir)> = None;
+        let entries = fs::read_dir(root).map_err(|e| Error::io(root, e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| Error::io(root, e))?;
+            let run = RunDir {
+                path: format!("{root}/{}", entry.file_name().to_string_lossy()),
+            };
+            // not every directory under the root is a run
+            let Ok(manifest) = run.manifest() else {
+                continue;
+            };
+            if manifest.seed == seed
+                && latest
+                    .as_ref()
+                    .is_none_or(|(started, _)| manifest.started > *started)
+            {
+                latest = Some((manifest.started, run));
+            }
+        }
+        latest.map(|(_, run)| run).ok_or(Error::NoRun {
+            root: root.to_string(),
+            seed,
+        })
+    }
+
+    pub fn file(&self, name: &str) -> String {
+        format!("{}/{name}", self.path)
+    }
+
+    // the directory the run is in
+    pub fn root(&self) -> String {
+        Path::new(&self.path)
+            .parent()
+            .map(|path| path.to_string_lossy().to_string())
+            .filter(|path| !path.is_empty())
+            .unwrap_or_else(|| ".".to_string())
+    }
+
+    pub fn name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn checkpoints(&self) -> String {
+        self.file("checkpoints")
+    }
+
+    pub fn manifest(&self) -> Result<Manifest> {
+        let path = self.file(MANIFEST);
+        let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+        serde_json::from_str(&text).map_err(|e| Error::json(&path, e))
+    }
+
+    fn write_manifest(&self, manifest: &Manifest) -> Result<()> {
+        let path = self.file(MANIFEST);
+        let text = serde_json::to_string_pretty(manifest).map_err(|e| Error::json(&path, e))?;
+        fs::write(&path, text).map_err(|e| Error::io(&path, e))
+    }
+
+    // marks the run as going again after a checkpoint
+    pub fn resumed(&self) -> Result<()> {
+        let mut manifest = self.manifest()?;
+        manifest.resumed.push(iso_now());
+        manifest.finished = None;
+        manifest.exit_reason = ExitReason::Running;
+        manifest.error = None;
+        self.write_manifest(&manifest)
+    }
+
+    /// Records in the manifest when and how the run ended.
+    pub fn finish(&self, outcome: &Result<RunEnd>) -> Result<()> {
+        let mut manifest = self.manifest()?;
+        manifest.finished = Some(iso_now());
+        match outcome {
+            Ok(end) => {
+                manifest.exit_reason = end.reason;
+                manifest.last_age = Some(end.last_age);
+            }
+            Err(error) => {
+                manifest.exit_reason = ExitReason::Error;
+                manifest.error = Some(error.to_string());
+            }
+        }
+        self.write_manifest(&manifest)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn iso_now() -> String {
+    timestamp(now(), "-", "T", ":") + "Z"
+}
+
+// UTC date and time of unix seconds, e.g. 2024-03-09T14:05:00 with ("-", "T", ":")
+fn timestamp(seconds: u64, date_sep: &str, middle: &str, time_sep: &str) -> String {
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // civil date from days since 1970-01-01, after Howard Hinnant's days_from_civil inverse
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{year:04}{date_sep}{month:02}{date_sep}{day:02}{middle}{:02}{time_sep}{:02}{time_sep}{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
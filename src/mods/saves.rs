@@ -0,0 +1,660 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+use crate::mods::blobs::Blob;
+use crate::mods::brains::{which_plasticity, Plasticity};
+use crate::mods::constants::Constants;
+use crate::mods::errors::{Error, Result};
+use crate::mods::genomes::{Body, Genome};
+use crate::mods::world::World;
+
+/// A save is MAGIC, the format version as a little-endian u32, then the bincode
+/// of the World of that version. Files without the header come from before saves
+/// were versioned and are read as version 1. Version 3 added hash_state.
+pub const MAGIC: &[u8; 8] = b"BLOBSAVE";
+pub const VERSION: u32 = 3;
+
+pub fn encode(world: &World) -> io::Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bincode::serialize_into(&mut bytes, world).map_err(io::Error::other)?;
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> io::Result<World> {
+    let (version, body) = match bytes.strip_prefix(MAGIC) {
+        Some(rest) if rest.len() >= 4 => {
+            let version = u32::from_le_bytes(rest[..4].try_into().unwrap());
+            (version, &rest[4..])
+        }
+        _ => (1, bytes),
+    };
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    match version {
+        1 => {
+            let world: v1::World = bincode::deserialize(body).map_err(invalid)?;
+            Ok(world.upgrade())
+        }
+        2 => {
+            let world: v2::World = bincode::deserialize(body).map_err(invalid)?;
+            Ok(world.upgrade())
+        }
+        VERSION => bincode::deserialize(body).map_err(invalid),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("save format version {version} is newer than this build ({VERSION})"),
+        )),
+    }
+}
+
+// Text versions of a save, picked by file extension
+enum TextFormat {
+    Json,
+    Ron,
+}
+
+impl TextFormat {
+    fn of(path: &str) -> Result<TextFormat> {
+        match path.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("json") => Ok(TextFormat::Json),
+            Some("ron") => Ok(TextFormat::Ron),
+            _ => Err(Error::Config {
+                field: path.to_string(),
+                message: "exports must end in .json or .ron".to_string(),
+            }),
+        }
+    }
+}
+
+// The version goes along so an export can be told apart from a later format
+#[derive(Serialize)]
+struct ExportRef<'a> {
+    version: u32,
+    world: &'a World,
+}
+
+// read first, to know which World the rest is
+#[derive(Deserialize)]
+struct ExportVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct Export<W> {
+    world: W,
+}
+
+/// Writes the world as pretty JSON or RON, for reading, diffing or editing by hand.
+pub fn export(world: &World, path: &str) -> Result<()> {
+    let export = ExportRef {
+        version: VERSION,
+        world,
+    };
+    let text = match TextFormat::of(path)? {
+        TextFormat::Json => {
+            serde_json::to_string_pretty(&export).map_err(|e| Error::json(path, e))?
+        }
+        TextFormat::Ron => ron::ser::to_string_pretty(&export, Default::default())
+            .map_err(|e| Error::ron(path, e))?,
+    };
+    fs::write(path, text).map_err(|e| Error::io(path, e))
+}
+
+// Reads back a world written by `export`, possibly edited since
+pub fn import(path: &str) -> Result<World> {
+    let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+    let format = TextFormat::of(path)?;
+    let world = match parse::<ExportVersion>(&format, &text, path)?.version {
+        2 => parse::<Export<v2::World>>(&format, &text, path)?
+            .world
+            .upgrade(),
+        VERSION => parse::<Export<World>>(&format, &text, path)?.world,
+        version => {
+            return Err(Error::Config {
+                field: format!("{path}: version"),
+                message: format!(
+                    "exported with format version {version}, this build reads 2 to {VERSION}"
+                ),
+            })
+        }
+    };
+    // the same checks constants from a file get, under the export's name
+    world.constants.validate().map_err(|error| match error {
+        Error::Config { field, message } => Error::Config {
+            field: format!("{path}: constants.{field}"),
+            message,
+        },
+        error => error,
+    })?;
+    check_shapes(&world, path)?;
+    Ok(world)
+}
+
+fn parse<T: DeserializeOwned>(format: &TextFormat, text: &str, path: &str) -> Result<T> {
+    match format {
+        TextFormat::Json => serde_json::from_str(text).map_err(|e| Error::json(path, e)),
+        TextFormat::Ron => ron::from_str(text).map_err(|e| Error::ron(path, e)),
+    }
+}
+
+// Hand edits can leave a world the simulation would index out of bounds on, so
+// every brain has to be wired and every scent field laid out the way the
+// constants say
+fn check_shapes(world: &World, path: &str) -> Result<()> {
+    let network_shape = world.constants.network_shape();
+    let plastic = !matches!(which_plasticity(&world.constants), Plasticity::None);
+    let invalid = |field: String, message: String| Error::Config {
+        field: format!("{path}: {field}"),
+        message,
+    };
+    // one per species when they are on, each adding gradient inputs
+    let fields = if world.constants.pheromones { 2 } else { 0 };
+    if world.pheromones.len() != fields {
+        return Err(invalid(
+            "pheromones".to_string(),
+            format!(
+                "has {} fields, the constants make it {fields}",
+                world.pheromones.len()
+            ),
+        ));
+    }
+    for (i, field) in world.pheromones.iter().enumerate() {
+        if field.values.len() != field.cols * field.rows {
+            return Err(invalid(
+                format!("pheromones[{i}].values"),
+                format!(
+                    "has {} values for {} columns by {} rows",
+                    field.values.len(),
+                    field.cols,
+                    field.rows
+                ),
+            ));
+        }
+    }
+    for (i, blob) in world.blobs.iter().enumerate() {
+        // one vision ray per visual input, the other inputs come after them
+        let rays = blob.brain.neuron_angles.len();
+        if rays != world.constants.input_neurons_num as usize {
+            return Err(invalid(
+                format!("blobs[{i}].brain.neuron_angles"),
+                format!(
+                    "has {rays} rays, input_neurons_num is {}",
+                    world.constants.input_neurons_num
+                ),
+            ));
+        }
+        if blob.brain.network_shape != network_shape {
+            return Err(invalid(
+                format!("blobs[{i}].brain.network_shape"),
+                format!(
+                    "is {:?}, the constants make it {network_shape:?}",
+                    blob.brain.network_shape
+                ),
+            ));
+        }
+        let matrices = [
+            ("brain.weights", &blob.brain.weights, false),
+            ("genome.weights", &blob.genome.weights, false),
+            ("genome.plasticity", &blob.genome.plasticity, !plastic),
+        ];
+        for (field, weights, may_be_empty) in matrices {
+            let fits = shaped_like(weights, &network_shape) || may_be_empty && weights.is_empty();
+            if !fits {
+                return Err(invalid(
+                    format!("blobs[{i}].{field}"),
+                    format!("must be shaped like the network {network_shape:?}"),
+                ));
+            }
+        }
+        // only learning moves the brain's weights away from the inborn ones
+        if !plastic && blob.brain.weights != blob.genome.weights {
+            return Err(invalid(
+                format!("blobs[{i}].brain.weights"),
+                "must equal genome.weights when brains aren't plastic".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// one matrix per layer, a row per neuron it feeds with a weight per neuron of the layer before
+fn shaped_like(weights: &[Vec<Vec<f32>>], network_shape: &[i32]) -> bool {
+    weights.len() + 1 == network_shape.len()
+        && weights
+            .iter()
+            .zip(network_shape.windows(2))
+            .all(|(matrix, layers)| {
+                matrix.len() == layers[1] as usize
+                    && matrix.iter().all(|row| row.len() == layers[0] as usize)
+            })
+}
+
+/// The structs as the first release saved them, frozen so old files keep parsing
+/// whatever happens to the live ones.
+mod v1 {
+    use serde::{Deserialize, Serialize};
+
+    use super::v2;
+    use super::*;
+    use crate::mods::activations::Activation;
+    use crate::mods::blobs::BlobType;
+    use crate::mods::brains;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Constants {
+        pub seed: i32,
+        pub reproduction_distance: f32,
+        pub step_size: f32,
+        pub food_energy: f32,
+        pub neuron_length: f32,
+        pub world_shape: (f32, f32),
+        pub input_neurons_num: i32,
+        pub motion_energy_cost: f32,
+        pub prey_base_energy_gain: f32,
+        pub predator_base_energy_loss: f32,
+        pub mutation_rate: f32,
+        pub ages: i32,
+        pub num_predators: i32,
+        pub num_prey: i32,
+        pub max_speed: f32,
+        pub max_angle_diff: f32,
+        pub graph_neurons: bool,
+        pub activation: String,
+        pub render_every: i32,
+        pub dump_frames: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Brain {
+        pub network_shape: Vec<i32>,
+        pub neuron_separation_radians: f32,
+        pub weights: Vec<Vec<Vec<f32>>>,
+        pub neuron_angles: Vec<f32>,
+        pub neuron_length: f32,
+        pub activation: Activation,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Blob {
+        pub brain: Brain,
+        pub position: (f32, f32),
+        pub angle: f32,
+        pub blob_type: BlobType,
+        pub energy: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct World {
+        pub blobs: Vec<Blob>,
+        pub shape: (f32, f32),
+        pub constants: Constants,
+    }
+
+    impl Constants {
+        // Everything added in version 2 keeps the original behaviour. These are
+        // literal values on purpose: what a version 1 save upgrades to must not
+        // move when the shipped constants.json or the defaults in code do.
+        pub fn upgrade(self) -> v2::Constants {
+            v2::Constants {
+                seed: self.seed,
+                reproduction_distance: self.reproduction_distance,
+                step_size: self.step_size,
+                food_energy: self.food_energy,
+                neuron_length: self.neuron_length,
+                world_shape: self.world_shape,
+                input_neurons_num: self.input_neurons_num,
+                motion_energy_cost: self.motion_energy_cost,
+                prey_base_energy_gain: self.prey_base_energy_gain,
+                predator_base_energy_loss: self.predator_base_energy_loss,
+                mutation_rate: self.mutation_rate,
+                ages: self.ages,
+                num_predators: self.num_predators,
+                num_prey: self.num_prey,
+                max_speed: self.max_speed,
+                max_angle_diff: self.max_angle_diff,
+                graph_neurons: self.graph_neurons,
+                activation: self.activation,
+                render_every: self.render_every,
+                dump_frames: self.dump_frames,
+                controlled_prey: 0,
+                controlled_predators: 0,
+                signal_channels: 0,
+                signal_range: 30.0,
+                signal_energy_cost: 0.001,
+                pheromones: false,
+                pheromone_cell_size: 8.0,
+                pheromone_diffusion: 0.1,
+                pheromone_decay: 0.02,
+                pheromone_deposit: 0.1,
+                graph_pheromones: false,
+                size_dependent_predation: false,
+                predation_size_exponent: 2.0,
+                failed_attack_cost: 0.05,
+                predation_injury: 0.1,
+                handling_time: 0,
+                satiation_cap: 0.0,
+                digestion_rate: 0.05,
+                mutation_operator: "uniform".to_string(),
+                gene_mutation_probability: 1.0,
+                large_mutation_probability: 0.0,
+                large_mutation_scale: 10.0,
+                weight_bound: 0.0,
+                self_adaptive_mutation: false,
+                mutation_step_tau: 0.2,
+                min_mutation_step: 0.001,
+                heritable_body: false,
+                body_mutation_rate: 0.05,
+                speed_upkeep: 0.0002,
+                turn_upkeep: 0.001,
+                metabolism_upkeep: 0.001,
+                plasticity: "none".to_string(),
+                hebbian_rate: 0.01,
+                plastic_weight_bound: 5.0,
+                phylogeny: false,
+                prune_phylogeny: false,
+                log_events: false,
+                log_columns: [
+                    "population",
+                    "energy",
+                    "predation",
+                    "mutation",
+                    "body",
+                    "demography",
+                    "energy_range",
+                    "movement",
+                    "lineage",
+                ]
+                .iter()
+                .map(|set| set.to_string())
+                .collect(),
+                checkpoint_every: 0,
+                checkpoints_kept: 3,
+            }
+        }
+    }
+
+    impl World {
+        // Brains become genomes with the default body; the blobs are the founders
+        // of a fresh lineage
+        pub fn upgrade(self) -> super::World {
+            let constants = self.constants.upgrade().upgrade();
+            let blobs = self
+                .blobs
+                .into_iter()
+                .map(|blob| {
+                    let genome = Genome {
+                        weights: blob.brain.weights.clone(),
+                        neuron_separation_radians: blob.brain.neuron_separation_radians,
+                        mutation_step: constants.mutation_rate,
+                        body: Body::from_constants(&constants),
+                        plasticity: Vec::new(),
+                    };
+                    let brain = brains::Brain::new(
+                        &genome,
+                        blob.brain.network_shape,
+                        blob.brain.neuron_angles.len() as i32,
+                        blob.brain.neuron_length,
+                        blob.brain.activation,
+                    );
+                    super::Blob::new(
+                        genome,
+                        brain,
+                        blob.position,
+                        blob.angle,
+                        blob.blob_type,
+                        blob.energy,
+                    )
+                })
+                .collect();
+            super::World::new(blobs, constants)
+        }
+    }
+}
+
+/// The structs of version 2, which only differ from the live ones in the constants.
+mod v2 {
+    use serde::{Deserialize, Serialize};
+
+    use crate::mods::blobs::Blob;
+    use crate::mods::pheromones::PheromoneField;
+    use crate::mods::phylogeny::Phylogeny;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct Constants {
+        pub seed: i32,
+        pub reproduction_distance: f32,
+        pub step_size: f32,
+        pub food_energy: f32,
+        pub neuron_length: f32,
+        pub world_shape: (f32, f32),
+        pub input_neurons_num: i32,
+        pub motion_energy_cost: f32,
+        pub prey_base_energy_gain: f32,
+        pub predator_base_energy_loss: f32,
+        pub mutation_rate: f32,
+        pub ages: i32,
+        pub num_predators: i32,
+        pub num_prey: i32,
+        pub max_speed: f32,
+        pub max_angle_diff: f32,
+        pub graph_neurons: bool,
+        pub activation: String,
+        pub render_every: i32,
+        pub dump_frames: bool,
+        pub controlled_prey: i32,
+        pub controlled_predators: i32,
+        pub signal_channels: i32,
+        pub signal_range: f32,
+        pub signal_energy_cost: f32,
+        pub pheromones: bool,
+        pub pheromone_cell_size: f32,
+        pub pheromone_diffusion: f32,
+        pub pheromone_decay: f32,
+        pub pheromone_deposit: f32,
+        pub graph_pheromones: bool,
+        pub size_dependent_predation: bool,
+        pub predation_size_exponent: f32,
+        pub failed_attack_cost: f32,
+        pub predation_injury: f32,
+        pub handling_time: i32,
+        pub satiation_cap: f32,
+        pub digestion_rate: f32,
+        pub mutation_operator: String,
+        pub gene_mutation_probability: f32,
+        pub large_mutation_probability: f32,
+        pub large_mutation_scale: f32,
+        pub weight_bound: f32,
+        pub self_adaptive_mutation: bool,
+        pub mutation_step_tau: f32,
+        pub min_mutation_step: f32,
+        pub heritable_body: bool,
+        pub body_mutation_rate: f32,
+        pub speed_upkeep: f32,
+        pub turn_upkeep: f32,
+        pub metabolism_upkeep: f32,
+        pub plasticity: String,
+        pub hebbian_rate: f32,
+        pub plastic_weight_bound: f32,
+        pub phylogeny: bool,
+        pub prune_phylogeny: bool,
+        pub log_events: bool,
+        pub log_columns: Vec<String>,
+        pub checkpoint_every: i32,
+        pub checkpoints_kept: usize,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct World {
+        pub blobs: Vec<Blob>,
+        pub shape: (f32, f32),
+        pub constants: Constants,
+        pub pheromones: Vec<PheromoneField>,
+        pub next_id: u64,
+        pub phylogeny: Phylogeny,
+    }
+
+    impl Constants {
+        // state hashes are off, as no run before version 3 recorded them
+        pub fn upgrade(self) -> super::Constants {
+            super::Constants {
+                seed: self.seed,
+                reproduction_distance: self.reproduction_distance,
+                step_size: self.step_size,
+                food_energy: self.food_energy,
+                neuron_length: self.neuron_length,
+                world_shape: self.world_shape,
+                input_neurons_num: self.input_neurons_num,
+                motion_energy_cost: self.motion_energy_cost,
+                prey_base_energy_gain: self.prey_base_energy_gain,
+                predator_base_energy_loss: self.predator_base_energy_loss,
+                mutation_rate: self.mutation_rate,
+                ages: self.ages,
+                num_predators: self.num_predators,
+                num_prey: self.num_prey,
+                max_speed: self.max_speed,
+                max_angle_diff: self.max_angle_diff,
+                graph_neurons: self.graph_neurons,
+                activation: self.activation,
+                render_every: self.render_every,
+                dump_frames: self.dump_frames,
+                controlled_prey: self.controlled_prey,
+                controlled_predators: self.controlled_predators,
+                signal_channels: self.signal_channels,
+                signal_range: self.signal_range,
+                signal_energy_cost: self.signal_energy_cost,
+                pheromones: self.pheromones,
+                pheromone_cell_size: self.pheromone_cell_size,
+                pheromone_diffusion: self.pheromone_diffusion,
+                pheromone_decay: self.pheromone_decay,
+                pheromone_deposit: self.pheromone_deposit,
+                graph_pheromones: self.graph_pheromones,
+                size_dependent_predation: self.size_dependent_predation,
+                predation_size_exponent: self.predation_size_exponent,
+                failed_attack_cost: self.failed_attack_cost,
+                predation_injury: self.predation_injury,
+                handling_time: self.handling_time,
+                satiation_cap: self.satiation_cap,
+                digestion_rate: self.digestion_rate,
+                mutation_operator: self.mutation_operator,
+                gene_mutation_probability: self.gene_mutation_probability,
+                large_mutation_probability: self.large_mutation_probability,
+                large_mutation_scale: self.large_mutation_scale,
+                weight_bound: self.weight_bound,
+                self_adaptive_mutation: self.self_adaptive_mutation,
+                mutation_step_tau: self.mutation_step_tau,
+                min_mutation_step: self.min_mutation_step,
+                heritable_body: self.heritable_body,
+                body_mutation_rate: self.body_mutation_rate,
+                speed_upkeep: self.speed_upkeep,
+                turn_upkeep: self.turn_upkeep,
+                metabolism_upkeep: self.metabolism_upkeep,
+                plasticity: self.plasticity,
+                hebbian_rate: self.hebbian_rate,
+                plastic_weight_bound: self.plastic_weight_bound,
+                phylogeny: self.phylogeny,
+                prune_phylogeny: self.prune_phylogeny,
+                log_events: self.log_events,
+                log_columns: self.log_columns,
+                checkpoint_every: self.checkpoint_every,
+                checkpoints_kept: self.checkpoints_kept,
+                hash_state: false,
+            }
+        }
+    }
+
+    impl World {
+        pub fn upgrade(self) -> super::World {
+            super::World {
+                blobs: self.blobs,
+                shape: self.shape,
+                constants: self.constants.upgrade(),
+                pheromones: self.pheromones,
+                next_id: self.next_id,
+                phylogeny: self.phylogeny,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::activations::Activation;
+    use crate::mods::blobs::BlobType;
+
+    // a headerless bincode file, as the first release wrote them
+    fn v1_save() -> Vec<u8> {
+        let constants = v1::Constants {
+            seed: 5,
+            reproduction_distance: 1.0,
+            step_size: 0.5,
+            food_energy: 1.0,
+            neuron_length: 20.0,
+            world_shape: (100.0, 50.0),
+            input_neurons_num: 2,
+            motion_energy_cost: 0.007,
+            prey_base_energy_gain: 0.03,
+            predator_base_energy_loss: 0.004,
+            mutation_rate: 0.3,
+            ages: 10,
+            num_predators: 1,
+            num_prey: 1,
+            max_speed: 5.0,
+            max_angle_diff: 0.3,
+            graph_neurons: false,
+            activation: "relu".to_string(),
+            render_every: 0,
+            dump_frames: false,
+        };
+        let blob = |blob_type, position| v1::Blob {
+            brain: v1::Brain {
+                network_shape: vec![2, 2],
+                neuron_separation_radians: 0.1,
+                weights: vec![vec![vec![0.5, -0.25], vec![1.0, 0.0]]],
+                neuron_angles: vec![-0.05, 0.05],
+                neuron_length: 20.0,
+                activation: Activation::ReLu,
+            },
+            position,
+            angle: 1.0,
+            blob_type,
+            energy: 2.0,
+        };
+        let world = v1::World {
+            blobs: vec![
+                blob(BlobType::Prey, (10.0, 10.0)),
+                blob(BlobType::Predator, (40.0, 20.0)),
+            ],
+            shape: (100.0, 50.0),
+            constants,
+        };
+        bincode::serialize(&world).unwrap()
+    }
+
+    #[test]
+    fn v1_save_upgrades_and_round_trips() {
+        let world = decode(&v1_save()).unwrap();
+        assert_eq!(world.constants.seed, 5);
+        assert_eq!(world.constants.mutation_rate, 0.3);
+        assert_eq!(world.constants.plasticity, "none");
+        assert_eq!(world.blobs.len(), 2);
+        let predator = &world.blobs[1];
+        assert_eq!(predator.blob_type, BlobType::Predator);
+        assert_eq!(predator.position, (40.0, 20.0));
+        assert_eq!(predator.genome.weights, predator.brain.weights);
+        assert_eq!(predator.genome.mutation_step, 0.3);
+
+        // once upgraded it is saved, and read back, in the current format
+        let bytes = encode(&world).unwrap();
+        assert!(bytes.starts_with(MAGIC));
+        let reloaded = decode(&bytes).unwrap();
+        assert_eq!(
+            reloaded.constants.to_json().unwrap(),
+            world.constants.to_json().unwrap()
+        );
+        assert_eq!(reloaded.blobs.len(), 2);
+        assert_eq!(reloaded.blobs[1].brain.weights, predator.brain.weights);
+    }
+}
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::mods::blobs::{Blob, BlobType};
+use crate::mods::errors::{Error, Result};
+use crate::mods::frames::{Frame, FrameBlob, FrameReader};
+use crate::mods::genomes::Body;
+use crate::mods::world::World;
+
+// What one species did during an update, counted as it happens
+#[derive(Clone, Default, Debug)]
+pub struct Demography {
+    pub births: u32,
+    pub killed: u32,
+    pub starved: u32,
+    pub kills: u32,
+    pub speed_sum: f32,
+    // absolute heading change, left and right turns don't cancel out
+    pub turn_sum: f32,
+    pub moved: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct SpeciesStats {
+    pub count: usize,
+    pub births: u32,
+    pub killed: u32,
+    pub starved: u32,
+    pub kills: u32,
+    pub mean_energy: f32,
+    pub min_energy: f32,
+    pub median_energy: f32,
+    pub max_energy: f32,
+    pub mean_speed: f32,
+    pub mean_turn: f32,
+    pub mean_generation: f32,
+    pub handling: usize,
+    pub mean_satiety: f32,
+    pub mean_mutation_step: f32,
+    pub var_mutation_step: f32,
+    pub mean_body: Body,
+}
+
+impl SpeciesStats {
+    fn collect(blobs: &[&Blob], demography: &Demography) -> SpeciesStats {
+        let mut stats = SpeciesStats {
+            count: blobs.len(),
+            births: demography.births,
+            killed: demography.killed,
+            starved: demography.starved,
+            kills: demography.kills,
+            ..Default::default()
+        };
+        if demography.moved > 0 {
+            stats.mean_speed = demography.speed_sum / demography.moved as f32;
+            stats.mean_turn = demography.turn_sum / demography.moved as f32;
+        }
+        if blobs.is_empty() {
+            return stats;
+        }
+
+        let n = blobs.len() as f32;
+        let mean = |f: &dyn Fn(&Blob) -> f32| blobs.iter().map(|b| f(b)).sum::<f32>() / n;
+        stats.energy_range(blobs.iter().map(|b| b.energy).collect());
+        stats.mean_energy = mean(&|b| b.energy);
+        stats.mean_generation = mean(&|b| b.generation as f32);
+        stats.handling = blobs.iter().filter(|b| b.handling > 0).count();
+        stats.mean_satiety = mean(&|b| b.satiety);
+        stats.mean_mutation_step = mean(&|b| b.genome.mutation_step);
+        stats.var_mutation_step =
+            mean(&|b| (b.genome.mutation_step - stats.mean_mutation_step).powi(2));
+        stats.mean_body = Body {
+            body_mass: mean(&|b| b.genome.body.body_mass),
+            metabolism: mean(&|b| b.genome.body.metabolism),
+            top_speed: mean(&|b| b.genome.body.top_speed),
+            turn_rate: mean(&|b| b.genome.body.turn_rate),
+        };
+        stats
+    }
+
+    // only what a frame records: count, energies, generations and bodies
+    fn from_frame(blobs: &[&FrameBlob]) -> SpeciesStats {
+        let mut stats = SpeciesStats {
+            count: blobs.len(),
+            ..Default::default()
+        };
+        if blobs.is_empty() {
+            return stats;
+        }
+
+        let n = blobs.len() as f32;
+        let mean = |f: &dyn Fn(&FrameBlob) -> f32| blobs.iter().map(|b| f(b)).sum::<f32>() / n;
+        stats.energy_range(blobs.iter().map(|b| b.energy).collect());
+        stats.mean_energy = mean(&|b| b.energy);
+        stats.mean_generation = mean(&|b| b.generation as f32);
+        stats.mean_body = Body {
+            body_mass: mean(&|b| b.body_mass),
+            metabolism: mean(&|b| b.metabolism),
+            top_speed: mean(&|b| b.top_speed),
+            turn_rate: mean(&|b| b.turn_rate),
+        };
+        stats
+    }
+
+    // min, median and max of a non-empty set of energies
+    fn energy_range(&mut self, mut energies: Vec<f32>) {
+        energies.sort_by(f32::total_cmp);
+        let middle = energies.len() / 2;
+        self.median_energy = if energies.len().is_multiple_of(2) {
+            (energies[middle - 1] + energies[middle]) / 2.0
+        } else {
+            energies[middle]
+        };
+        self.min_energy = energies[0];
+        self.max_energy = energies[energies.len() - 1];
+    }
+}
+
+/// The state of the population after one update, as logged to a run's stats.csv.
+#[derive(Clone, Default, Serialize, Deserialize, Debug)]
+pub struct Stats {
+    pub age: i32,
+    pub prey: SpeciesStats,
+    pub predators: SpeciesStats,
+}
+
+// The column sets `log_columns` can list, in the order of the original log
+pub const COLUMN_SETS: [&str; 9] = [
+    "population",
+    "energy",
+    "predation",
+    "mutation",
+    "body",
+    "demography",
+    "energy_range",
+    "movement",
+    "lineage",
+];
+
+// The column sets a frames file holds the data for
+pub const FRAME_COLUMN_SETS: [&str; 5] =
+    ["population", "energy", "body", "energy_range", "lineage"];
+
+impl Stats {
+    pub fn from_frame(frame: &Frame) -> Stats {
+        let (preys, preds): (Vec<&FrameBlob>, Vec<&FrameBlob>) =
+            frame.blobs.iter().partition(|b| b.is_prey);
+        Stats {
+            age: frame.age as i32,
+            prey: SpeciesStats::from_frame(&preys),
+            predators: SpeciesStats::from_frame(&preds),
+        }
+    }
+
+    /// Writes the log columns a frames file can give, one row per recorded age, to
+    /// `output` or stdout, so stats can be had again from a run without re-simulating it.
+    pub fn write_frames_csv(
+        frames_path: &str,
+        sets: &[String],
+        output: Option<&str>,
+    ) -> Result<()> {
+        if let Some(set) = sets
+            .iter()
+            .find(|set| !FRAME_COLUMN_SETS.contains(&set.as_str()))
+        {
+            return Err(Error::Config {
+                field: "columns".to_string(),
+                message: format!("frames don't record {set}, use some of {FRAME_COLUMN_SETS:?}"),
+            });
+        }
+        let output_name = output.unwrap_or("stdout");
+        let write_error = |e| Error::io(output_name, e);
+        let mut out: Box<dyn Write> = match output {
+            Some(path) => Box::new(BufWriter::new(File::create(path).map_err(write_error)?)),
+            None => Box::new(io::stdout().lock()),
+        };
+        writeln!(out, "{}", Stats::csv_header(sets)?).map_err(write_error)?;
+        let reader = FrameReader::open(frames_path).map_err(|e| Error::io(frames_path, e))?;
+        for frame in reader {
+            let frame = frame.map_err(|e| Error::io(frames_path, e))?;
+            writeln!(out, "{}", Stats::from_frame(&frame).csv_row(sets)?).map_err(write_error)?;
+        }
+        out.flush().map_err(write_error)
+    }
+
+    pub fn collect(world: &World, age: i32, demography: &[Demography; 2]) -> Stats {
+        let (preys, preds): (Vec<&Blob>, Vec<&Blob>) = world
+            .blobs
+            .iter()
+            .partition(|b| b.blob_type == BlobType::Prey);
+        Stats {
+            age,
+            prey: SpeciesStats::collect(&preys, &demography[BlobType::Prey as usize]),
+            predators: SpeciesStats::collect(&preds, &demography[BlobType::Predator as usize]),
+        }
+    }
+
+    // (name, value) pairs of one column set, None if there is no such set
+    fn columns(&self, set: &str) -> Option<Vec<(String, String)>> {
+        let species = [("prey", &self.prey), ("pred", &self.predators)];
+        let per_species = |names: &[&str], value: &dyn Fn(&SpeciesStats, &str) -> String| {
+            species
+                .iter()
+                .flat_map(|(prefix, stats)| {
+                    names
+                        .iter()
+                        .map(move |name| (name.replace('*', prefix), value(stats, name)))
+                })
+                .collect::<Vec<_>>()
+        };
+        let columns = match set {
+            "population" => vec![
+                ("prey".to_string(), self.prey.count.to_string()),
+                ("predators".to_string(), self.predators.count.to_string()),
+            ],
+            "energy" => per_species(&["mean_*_energy"], &|s, _| s.mean_energy.to_string()),
+            "predation" => vec![
+                (
+                    "handling_predators".to_string(),
+                    self.predators.handling.to_string(),
+                ),
+                (
+                    "mean_pred_satiety".to_string(),
+                    self.predators.mean_satiety.to_string(),
+                ),
+            ],
+            "mutation" => per_species(
+                &["mean_*_mutation_step", "var_*_mutation_step"],
+                &|s, name| match name {
+                    "mean_*_mutation_step" => s.mean_mutation_step.to_string(),
+                    _ => s.var_mutation_step.to_string(),
+                },
+            ),
+            "body" => per_species(
+                &[
+                    "mean_*_body_mass",
+                    "mean_*_metabolism",
+                    "mean_*_top_speed",
+                    "mean_*_turn_rate",
+                ],
+                &|s, name| {
+                    let body = &s.mean_body;
+                    match name {
+                        "mean_*_body_mass" => body.body_mass,
+                        "mean_*_metabolism" => body.metabolism,
+                        "mean_*_top_speed" => body.top_speed,
+                        _ => body.turn_rate,
+                    }
+                    .to_string()
+                },
+            ),
+            "demography" => vec![
+                ("prey_births".to_string(), self.prey.births.to_string()),
+                ("prey_killed".to_string(), self.prey.killed.to_string()),
+                ("prey_starved".to_string(), self.prey.starved.to_string()),
+                ("pred_births".to_string(), self.predators.births.to_string()),
+                (
+                    "pred_starved".to_string(),
+                    self.predators.starved.to_string(),
+                ),
+                ("kills".to_string(), self.predators.kills.to_string()),
+            ],
+            "energy_range" => per_species(
+                &["min_*_energy", "median_*_energy", "max_*_energy"],
+                &|s, name| match name {
+                    "min_*_energy" => s.min_energy.to_string(),
+                    "median_*_energy" => s.median_energy.to_string(),
+                    _ => s.max_energy.to_string(),
+                },
+            ),
+            "movement" => per_species(&["mean_*_speed", "mean_*_turn"], &|s, name| match name {
+                "mean_*_speed" => s.mean_speed.to_string(),
+                _ => s.mean_turn.to_string(),
+            }),
+            "lineage" => per_species(&["mean_*_generation"], &|s, _| {
+                s.mean_generation.to_string()
+            }),
+            _ => return None,
+        };
+        Some(columns)
+    }
+
+    fn csv_line(&self, sets: &[String], pick: fn((String, String)) -> String) -> Result<String> {
+        let mut line = vec![pick(("age".to_string(), self.age.to_string()))];
+        for set in sets {
+            let columns = self.columns(set).ok_or_else(|| Error::Config {
+                field: "log_columns".to_string(),
+                message: format!("unknown column set {set}, use one of {COLUMN_SETS:?}"),
+            })?;
+            line.extend(columns.into_iter().map(pick));
+        }
+        Ok(line.join(","))
+    }
+
+    pub fn csv_header(sets: &[String]) -> Result<String> {
+        Stats::default().csv_line(sets, |(name, _)| name)
+    }
+
+    pub fn csv_row(&self, sets: &[String]) -> Result<String> {
+        self.csv_line(sets, |(_, value)| value)
+    }
+}
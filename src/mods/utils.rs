@@ -1,17 +1,17 @@
 use crate::mods::blobs::Blob;
-pub fn matrix_prod(matrix: &Vec<Vec<f32>>, vector: &Vec<f32>) -> Vec<f32> {
+pub fn matrix_prod(matrix: &[Vec<f32>], vector: &[f32]) -> Vec<f32> {
     let mut output: Vec<f32> = Vec::new();
-    for i in 0..matrix.len() {
+    for row in matrix {
         let mut s = 0.0;
         for k in 0..vector.len() {
-            s += matrix[i][k] * vector[k];
+            s += row[k] * vector[k];
         }
         output.push(s);
     }
     output
 }
 
-pub fn _matrix_sum(matrix1: &Vec<Vec<f32>>, matrix2: &Vec<Vec<f32>>) -> Vec<Vec<f32>> {
+pub fn _matrix_sum(matrix1: &[Vec<f32>], matrix2: &[Vec<f32>]) -> Vec<Vec<f32>> {
     // Check if dimensions match
     if matrix1.len() != matrix2.len() || matrix1[0].len() != matrix2[0].len() {
         panic!("Matrices dimensions do not match");
@@ -30,44 +30,6 @@ pub fn _matrix_sum(matrix1: &Vec<Vec<f32>>, matrix2: &Vec<Vec<f32>>) -> Vec<Vec<
     output
 }
 
-pub fn sum_weights(
-    matrices1: &Vec<Vec<Vec<f32>>>,
-    matrices2: &Vec<Vec<Vec<f32>>>,
-) -> Vec<Vec<Vec<f32>>> {
-    // Check if both vectors have the same number of matrices
-    if matrices1.len() != matrices2.len() {
-        panic!("The vectors of matrices must have the same length");
-    }
-
-    // Initialize the output vector of matrices
-    let mut sum_matrices: Vec<Vec<Vec<f32>>> = Vec::new();
-
-    // Iterate over each matrix pair
-    for (matrix1, matrix2) in matrices1.iter().zip(matrices2.iter()) {
-        // Check if dimensions of each corresponding matrix pair match
-        if matrix1.len() != matrix2.len() || matrix1[0].len() != matrix2[0].len() {
-            panic!("All corresponding matrices must have the same dimensions");
-        }
-
-        // Initialize the sum matrix with the same dimensions as the input matrices
-        let rows = matrix1.len();
-        let cols = matrix1[0].len();
-        let mut sum_matrix = vec![vec![0.0; cols]; rows];
-
-        // Sum the corresponding elements of the matrices
-        for i in 0..rows {
-            for j in 0..cols {
-                sum_matrix[i][j] = matrix1[i][j] + matrix2[i][j];
-            }
-        }
-
-        // Add the sum matrix to the output vector
-        sum_matrices.push(sum_matrix);
-    }
-
-    sum_matrices
-}
-
 pub fn distance_to_segment(
     object_center: &(f32, f32),
     starting_point: &(f32, f32),
@@ -107,6 +69,29 @@ pub fn visual_neuron_activation(
     sum
 }
 
+// every other blob in range is heard on each channel, fading linearly with distance
+pub fn heard_signals(blobs: &[Blob], listener: &Blob, channels: usize, range: f32) -> Vec<f32> {
+    let mut heard = vec![0.0; channels];
+    if channels == 0 {
+        return heard;
+    }
+    for blob in blobs {
+        if std::ptr::eq(blob, listener) {
+            continue;
+        }
+        let distance = ((blob.position.0 - listener.position.0).powi(2)
+            + (blob.position.1 - listener.position.1).powi(2))
+        .sqrt();
+        if distance >= range {
+            continue;
+        }
+        for (channel, value) in blob.signal.iter().take(channels).enumerate() {
+            heard[channel] += value * (1.0 - distance / range);
+        }
+    }
+    heard
+}
+
 pub fn cap(val: f32, max: f32) -> f32 {
     let new = (val / max).fract() * max;
     if new < 0.0 {
@@ -1,33 +1,110 @@
-use crate::mods::{constants::load_constants, frames::FrameWriter};
+use crate::mods::{
+    agents::{Action, Controller, Observation},
+    checkpoints::{open_output, Checkpoint, OutputLengths, WorldRng},
+    errors::{Error, Result},
+    events::{Event, EventWriter},
+    frames::FrameWriter,
+    hashes,
+    pheromones::PheromoneField,
+    phylogeny::{Fate, Phylogeny},
+    render,
+    runs::{ExitReason, RunDir, RunEnd},
+    saves,
+    stats::{Demography, Stats},
+};
 
 use super::{
     activations::{sigmoid, tanh, which_activation},
     blobs::{Blob, BlobType},
-    brains::Brain,
-    constants::Constants,
+    brains::{which_plasticity, Brain, Plasticity},
+    constants::{Constants, LoadPolicy},
+    genomes::{Body, Genome, Mutation},
 };
-use bincode;
 use plotters::prelude::*;
 use rand::Rng;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::io::{self, Read, Write};
-use std::{collections::HashSet, usize};
-use std::{fs::File, io::BufWriter};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, Write};
+
+// What happened to an agent-controlled blob during one update
+#[derive(Clone, Default, Debug)]
+pub struct AgentReport {
+    pub kills: u32,
+    pub died: bool,
+    pub reproduced: bool,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct UpdateReport {
+    pub agents: HashMap<usize, AgentReport>,
+    // births, deaths and kills in the order they happened, only with constants.log_events
+    pub events: Vec<Event>,
+    // indexed by BlobType
+    pub demography: [Demography; 2],
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct World {
     pub blobs: Vec<Blob>,
     pub shape: (f32, f32),
     pub constants: Constants,
+    // one scent field per species, indexed by BlobType
+    pub pheromones: Vec<PheromoneField>,
+    // the id the next blob born will get
+    pub next_id: u64,
+    // every blob that ever lived, only kept when constants.phylogeny is on
+    pub phylogeny: Phylogeny,
 }
 
 impl World {
-    pub fn new(blobs: Vec<Blob>, constants: Constants) -> World {
+    // founders are numbered from 1 in the order given
+    pub fn new(mut blobs: Vec<Blob>, constants: Constants) -> World {
+        let mut phylogeny = Phylogeny::default();
+        for (i, blob) in blobs.iter_mut().enumerate() {
+            blob.id = i as u64 + 1;
+            if constants.phylogeny {
+                phylogeny.born(blob);
+            }
+        }
+        let pheromones = if constants.pheromones {
+            vec![PheromoneField::new(constants.world_shape, constants.pheromone_cell_size); 2]
+        } else {
+            Vec::new()
+        };
         World {
+            next_id: blobs.len() as u64 + 1,
             blobs,
             shape: constants.world_shape,
             constants,
+            pheromones,
+            phylogeny,
+        }
+    }
+
+    fn record_birth(&mut self, blob: &Blob, age: i32, report: &mut UpdateReport) {
+        report.demography[blob.blob_type as usize].births += 1;
+        if self.constants.phylogeny {
+            self.phylogeny.born(blob);
+        }
+        if self.constants.log_events {
+            report.events.push(Event::birth(blob, age));
+        }
+    }
+
+    fn record_death(&mut self, blob: &Blob, age: i32, fate: Fate, report: &mut UpdateReport) {
+        let demography = &mut report.demography[blob.blob_type as usize];
+        match fate {
+            Fate::Killed => demography.killed += 1,
+            Fate::Starved => demography.starved += 1,
+            _ => {}
+        }
+        if self.constants.phylogeny {
+            self.phylogeny.died(blob.id, age, fate);
+        }
+        if self.constants.log_events {
+            report.events.push(Event::death(blob, age, fate));
         }
     }
     fn get_indexes(&self) -> (Vec<usize>, Vec<usize>) {
@@ -63,12 +140,24 @@ impl World {
             .collect()
     }
 
-    fn gather_responses(&self, stimuli_list: Vec<Vec<f32>>) -> Vec<Vec<f32>> {
+    fn gather_responses(&mut self, stimuli_list: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let plastic = !matches!(which_plasticity(&self.constants), Plasticity::None);
+        let (rate, bound) = (
+            self.constants.hebbian_rate,
+            self.constants.plastic_weight_bound,
+        );
         let output_list: Vec<(usize, Vec<f32>)> = self
             .blobs
-            .par_iter()
+            .par_iter_mut()
             .enumerate()
-            .map(|(i, blob)| (i, blob.brain.synapse(&stimuli_list[i])))
+            .map(|(i, blob)| {
+                let mut activity = blob.brain.activity(&stimuli_list[i]);
+                if plastic {
+                    blob.brain
+                        .learn(&blob.genome.plasticity, &activity, rate, bound);
+                }
+                (i, activity.pop().unwrap_or_default())
+            })
             .collect();
         let mut sorted_output_list = output_list;
         sorted_output_list.sort_by_key(|(i, _)| *i);
@@ -78,27 +167,100 @@ impl World {
             .collect()
     }
 
-    fn move_blobs(&mut self, responses: Vec<Vec<f32>>) {
+    // the outputs after speed and turn are the signal channels; they are heard next age
+    fn emit_signals(&mut self, responses: &[Vec<f32>]) {
+        let cost = self.constants.signal_energy_cost;
+        let channels = self.constants.signal_channels as usize;
         self.blobs.par_iter_mut().enumerate().for_each(|(i, blob)| {
-            let response = &responses[i];
+            blob.signal = responses[i][2..2 + channels]
+                .iter()
+                .map(|&x| tanh(x))
+                .collect();
+            let loudness: f32 = blob.signal.iter().map(|s| s.abs()).sum();
+            blob.add_energy(-cost * loudness);
+        });
+    }
 
-            let (speed, angle_diff) = (
-                self.constants.max_speed * sigmoid(response[0]),
-                self.constants.max_angle_diff * tanh(response[1]),
-            );
-            blob.angle += angle_diff;
-            blob.step(
-                speed,
-                &self.shape,
-                self.constants.step_size,
-                self.constants.motion_energy_cost,
-            );
+    // the last output is how much scent the blob leaves where it stands
+    fn deposit_pheromones(&mut self, responses: &[Vec<f32>]) {
+        if self.pheromones.is_empty() {
+            return;
+        }
+        for (blob, response) in self.blobs.iter().zip(responses) {
+            let amount = self.constants.pheromone_deposit * sigmoid(response[response.len() - 1]);
+            self.pheromones[blob.blob_type as usize].deposit(blob.position, amount);
+        }
+    }
+
+    fn spread_pheromones(&mut self) {
+        let (diffusion, decay) = (
+            self.constants.pheromone_diffusion,
+            self.constants.pheromone_decay,
+        );
+        self.pheromones
+            .par_iter_mut()
+            .for_each(|field| field.step(diffusion, decay));
+    }
+
+    fn responses_to_actions(&self, responses: Vec<Vec<f32>>) -> Vec<Action> {
+        responses
+            .into_iter()
+            .zip(&self.blobs)
+            .map(|(response, blob)| Action {
+                speed: blob.genome.body.top_speed * sigmoid(response[0]),
+                turn: blob.genome.body.turn_rate * tanh(response[1]),
+            })
+            .collect()
+    }
+
+    fn external_actions(
+        &self,
+        age: i32,
+        stimuli_list: &[Vec<f32>],
+        actions: &mut [Action],
+        controller: &mut dyn Controller,
+    ) -> Result<()> {
+        // sequential and in blob order so a recorded agent log replays exactly
+        for (i, blob) in self.blobs.iter().enumerate() {
+            if let Some(agent) = blob.agent {
+                let observation = Observation {
+                    age,
+                    agent,
+                    blob_type: blob.blob_type,
+                    energy: blob.energy,
+                    stimuli: stimuli_list[i].clone(),
+                };
+                let action = controller.act(&observation).map_err(Error::Agent)?;
+                // external agents are bound by the same body as the brains
+                let body = &blob.genome.body;
+                actions[i] = Action {
+                    speed: action.speed.clamp(0.0, body.top_speed),
+                    turn: action.turn.clamp(-body.turn_rate, body.turn_rate),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn move_blobs(&mut self, actions: Vec<Action>, report: &mut UpdateReport) {
+        for (blob, action) in self.blobs.iter().zip(&actions) {
+            let demography = &mut report.demography[blob.blob_type as usize];
+            demography.speed_sum += action.speed;
+            demography.turn_sum += action.turn.abs();
+            demography.moved += 1;
+        }
+        self.blobs.par_iter_mut().enumerate().for_each(|(i, blob)| {
+            let Action { speed, turn } = actions[i];
+            blob.angle += turn;
+            // heavier bodies pay more to move
+            let cost = self.constants.motion_energy_cost * (1.0 + blob.genome.body.body_mass);
+            blob.step(speed, &self.shape, self.constants.step_size, cost);
         });
     }
 
     fn check_pred_interactions(
         (i, predator): (usize, &Blob),
-        preys: &Vec<&Blob>,
+        preys: &[&Blob],
     ) -> Vec<(usize, usize)> {
         let mut interactions = Vec::new();
         let predator_position = predator.position;
@@ -115,8 +277,8 @@ impl World {
 
     fn check_interactions(
         &self,
-        predator_indexes: &Vec<usize>,
-        prey_indexes: &Vec<usize>,
+        predator_indexes: &[usize],
+        prey_indexes: &[usize],
     ) -> Vec<(usize, usize)> {
         let preys: Vec<&Blob> = prey_indexes.iter().map(|&i| &self.blobs[i]).collect();
         let predators: Vec<&Blob> = predator_indexes.iter().map(|&i| &self.blobs[i]).collect();
@@ -129,7 +291,14 @@ impl World {
         interactions
     }
 
-    fn kills(&mut self, interactions: Vec<(usize, usize)>, prey_indexes: &Vec<usize>) {
+    fn kills(
+        &mut self,
+        interactions: Vec<(usize, usize)>,
+        prey_indexes: &[usize],
+        age: i32,
+        rng: &mut impl Rng,
+        report: &mut UpdateReport,
+    ) {
         //get vecs of mutable refferences to the preys and predators
         let mut preys = Vec::new();
         let mut predators = Vec::new();
@@ -143,9 +312,50 @@ impl World {
         //run through the interactions and feed the predators and mark the prey to kill
         let mut to_remove = HashSet::new();
         for (predator_idx, prey_idx) in interactions {
+            // a prey in reach of several predators is only eaten by the first
+            if to_remove.contains(&prey_indexes[prey_idx]) {
+                continue;
+            }
             let predator: &mut Blob = predators[predator_idx];
             let prey: &mut Blob = preys[prey_idx];
+            if !predator.can_attack(self.constants.satiation_cap) {
+                continue;
+            }
+            if self.constants.size_dependent_predation {
+                let p = predator.kill_probability(prey, self.constants.predation_size_exponent);
+                if rng.gen::<f32>() >= p {
+                    // the prey got away, and fighting back hurts more the bigger it is
+                    let injury =
+                        self.constants.predation_injury * (prey.energy - predator.energy).max(0.0);
+                    predator.add_energy(-self.constants.failed_attack_cost - injury);
+                    continue;
+                }
+            }
+            if self.constants.log_events {
+                report.events.push(Event::Kill {
+                    age,
+                    predator: predator.id,
+                    prey: prey.id,
+                    predator_energy: predator.energy,
+                    prey_energy: prey.energy,
+                    position: prey.position,
+                });
+            }
             predator.add_energy(self.constants.food_energy * prey.energy);
+            // the timer counts down at the start of every age, so this blocks the
+            // next handling_time ages and reads handling_time..1 in the brain input;
+            // at 0 there is no handling and a predator eats all it reaches
+            if self.constants.handling_time > 0 {
+                predator.handling = self.constants.handling_time + 1;
+            }
+            predator.satiety += prey.energy;
+            report.demography[BlobType::Predator as usize].kills += 1;
+            if let Some(agent) = predator.agent {
+                report.agents.entry(agent).or_default().kills += 1;
+            }
+            if let Some(agent) = prey.agent {
+                report.agents.entry(agent).or_default().died = true;
+            }
 
             //horrible way of writting the prey_idx´th prey position in the blobs array
             to_remove.insert(prey_indexes[prey_idx]);
@@ -159,40 +369,67 @@ impl World {
             if *idx >= self.blobs.len() {
                 println!("to remove: {:?}", to_remove)
             }
-            self.blobs.remove(*idx).die();
+            let prey = self.blobs.remove(*idx);
+            self.record_death(&prey, age, Fate::Killed, report);
+            prey.die();
         }
     }
 
-    fn reproduce_blobs(&mut self, rng: &mut impl Rng) {
+    fn reproduce_blobs(&mut self, age: i32, rng: &mut impl Rng, report: &mut UpdateReport) {
         let mut to_reproduce = Vec::new();
         for (blob_idx, blob) in self.blobs.iter().enumerate() {
             if blob.energy >= 2.0 {
                 to_reproduce.push(blob_idx);
             }
         }
+        let mutation = Mutation::from_constants(&self.constants);
         for blob_idx in to_reproduce.iter().rev() {
             let blob = self.blobs.remove(*blob_idx);
+            if let Some(agent) = blob.agent {
+                report.agents.entry(agent).or_default().reproduced = true;
+            }
+            self.record_death(&blob, age, Fate::Reproduced, report);
+            let ids = (self.next_id, self.next_id + 1);
+            self.next_id += 2;
             let (child1, child2) = blob.reproduce(
                 self.constants.reproduction_distance,
-                self.constants.mutation_rate,
+                &mutation,
+                ids,
+                age,
                 rng,
             );
+            self.record_birth(&child1, age, report);
+            self.record_birth(&child2, age, report);
             self.blobs.push(child1);
             self.blobs.push(child2);
         }
     }
 
+    // handling timers count down and the gut empties a little every age
+    fn digest(&mut self) {
+        let digestion_rate = self.constants.digestion_rate;
+        self.blobs.par_iter_mut().for_each(|blob| {
+            blob.handling = (blob.handling - 1).max(0);
+            blob.satiety = (blob.satiety - digestion_rate * blob.genome.body.metabolism).max(0.0);
+        })
+    }
+
     fn base_energy(&mut self) {
         self.blobs.par_iter_mut().for_each(|blob| {
             let energy = match blob.blob_type {
                 BlobType::Prey => self.constants.prey_base_energy_gain,
                 BlobType::Predator => -self.constants.predator_base_energy_loss,
             };
-            blob.add_energy(energy);
+            let upkeep = if self.constants.heritable_body {
+                blob.upkeep(&self.constants)
+            } else {
+                0.0
+            };
+            blob.add_energy(energy * blob.genome.body.metabolism - upkeep);
         })
     }
 
-    fn starved(&mut self) {
+    fn starved(&mut self, age: i32, report: &mut UpdateReport) {
         let mut starved_blobs_idxs = Vec::new();
         for (i, blob) in self.blobs.iter().enumerate() {
             if blob.energy < 0.0 {
@@ -200,13 +437,21 @@ impl World {
             }
         }
         for i in starved_blobs_idxs.iter().rev() {
-            self.blobs.remove(*i).die();
+            let blob = self.blobs.remove(*i);
+            if let Some(agent) = blob.agent {
+                report.agents.entry(agent).or_default().died = true;
+            }
+            self.record_death(&blob, age, Fate::Starved, report);
+            blob.die();
         }
     }
 
-    fn graph(&self, filename: &str, graph_neurons: bool) -> Result<(), Box<dyn std::error::Error>> {
-        let screen_shape = (1366, 768);
-        let drawing_area = BitMapBackend::new(filename, screen_shape).into_drawing_area();
+    fn graph(
+        &self,
+        filename: &str,
+        graph_neurons: bool,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let drawing_area = BitMapBackend::new(filename, render::SCREEN_SHAPE).into_drawing_area();
 
         // Fill the background with white color
         drawing_area.fill(&WHITE)?;
@@ -218,16 +463,16 @@ impl World {
         // Draw the mesh (the grid lines and labels)
         chart.configure_mesh().draw()?;
 
+        if self.constants.graph_pheromones {
+            render::draw_pheromones(&mut chart, &self.pheromones)?;
+        }
+
         // Draw the circles
         for blob in &self.blobs {
-            let color = match blob.blob_type {
-                BlobType::Prey => GREEN.mix(0.9).filled(),
-                BlobType::Predator => RED.mix(0.9).filled(),
-            };
             chart.draw_series(std::iter::once(Circle::new(
                 blob.position,
-                blob.radius() * (1366.0 / self.shape.0).round(),
-                color,
+                blob.radius() * render::scale(self.shape),
+                render::blob_style(blob.blob_type == BlobType::Prey),
             )))?;
 
             if graph_neurons {
@@ -250,7 +495,7 @@ impl World {
 
                     chart.draw_series(std::iter::once(PathElement::new(
                         vec![blob.position, (end_x, end_y)],
-                        &BLACK.mix(0.1),
+                        BLACK.mix(0.1),
                     )))?;
                 }
             }
@@ -261,147 +506,303 @@ impl World {
         Ok(())
     }
 
-    pub fn update(&mut self, age: i32, rng: &mut impl Rng) {
+    // blobs with an agent slot are steered by the controller when there is one
+    pub fn update(
+        &mut self,
+        age: i32,
+        rng: &mut impl Rng,
+        controller: Option<&mut (dyn Controller + '_)>,
+    ) -> Result<UpdateReport> {
+        let mut report = UpdateReport::default();
         let (predator_indexes, prey_indexes) = self.get_indexes();
 
+        self.digest();
+
         let stimuli_list = self.gather_stimuli();
         // println!("{stimuli_list:?}");
 
-        let responses = self.gather_responses(stimuli_list);
+        let responses = self.gather_responses(&stimuli_list);
         // println!("{responses:?}");
 
-        self.move_blobs(responses);
+        self.emit_signals(&responses);
+
+        self.deposit_pheromones(&responses);
+
+        let mut actions = self.responses_to_actions(responses);
+        if let Some(controller) = controller {
+            self.external_actions(age, &stimuli_list, &mut actions, controller)?;
+        }
+
+        self.move_blobs(actions, &mut report);
 
         let interactions = self.check_interactions(&predator_indexes, &prey_indexes);
 
-        self.kills(interactions, &prey_indexes);
+        self.kills(interactions, &prey_indexes, age, rng, &mut report);
 
         self.base_energy();
 
-        self.starved();
+        self.starved(age, &mut report);
 
-        self.reproduce_blobs(rng);
-        if self.constants.render_every > 0 && age % self.constants.render_every == 0 {
-            let frame_number = age / self.constants.render_every;
-            let filename = format!("./animation/frame{:04}.png", frame_number);
-            println!("{filename}");
-            self.graph(&filename, self.constants.graph_neurons)
-                .expect("something wong with graphing");
-        }
+        self.reproduce_blobs(age, rng, &mut report);
+
+        self.spread_pheromones();
+        Ok(report)
+    }
+
+    // all outputs go into the run directory
+    pub fn evolve(
+        &mut self,
+        run_dir: &RunDir,
+        rng: &mut WorldRng,
+        controller: Option<&mut (dyn Controller + '_)>,
+    ) -> Result<RunEnd> {
+        self.run(run_dir, 0, None, rng, controller)
     }
 
-    pub fn evolve(&mut self, rng: &mut impl Rng) {
+    // carries on a checkpointed run, cutting its outputs back to where the checkpoint was taken
+    pub fn resume(
+        run_dir: &RunDir,
+        checkpoint: Checkpoint,
+        controller: Option<&mut (dyn Controller + '_)>,
+    ) -> Result<RunEnd> {
+        let Checkpoint {
+            mut world,
+            mut rng,
+            age,
+            outputs,
+        } = checkpoint;
+        world.run(run_dir, age, Some(&outputs), &mut rng, controller)
+    }
+
+    fn run(
+        &mut self,
+        run_dir: &RunDir,
+        start_age: i32,
+        resume: Option<&OutputLengths>,
+        rng: &mut WorldRng,
+        mut controller: Option<&mut (dyn Controller + '_)>,
+    ) -> Result<RunEnd> {
         // Write stats to csv
-        let file = File::create(format!("runs/seed{}.csv", self.constants.seed))
-            .expect("couldn't create log");
+        let log_path = run_dir.file("stats.csv");
+        let file =
+            open_output(&log_path, resume.map(|r| r.log)).map_err(|e| Error::io(&log_path, e))?;
         let mut log = BufWriter::new(file);
-        writeln!(log, "age,prey,predators,mean_prey_energy,mean_pred_energy").unwrap();
+        let columns = &self.constants.log_columns;
+        if resume.is_none() {
+            writeln!(log, "{}", Stats::csv_header(columns)?)
+                .map_err(|e| Error::io(&log_path, e))?;
+        }
 
         // Write state to bin if dump_frames
+        let frames_path = run_dir.file("frames.bin");
         let mut frame_writer = if self.constants.dump_frames {
-            let filename = format!("runs/frames/{}.bin", self.constants.seed);
-            std::fs::create_dir_all("runs/frames").expect("couldn't create runs/frames dir");
-            Some(
-                FrameWriter::new(&filename)
-                    .expect(&format!("Couldnt write to or create {}", filename)),
-            )
+            let file = open_output(&frames_path, resume.map(|r| r.frames))
+                .map_err(|e| Error::io(&frames_path, e))?;
+            Some(FrameWriter::from_file(file))
         } else {
             None
         };
 
-        for age in 0..self.constants.ages {
-            self.update(age, rng);
-            let blobs_count = self.blobs.len();
-            let (preys, preds): (Vec<_>, Vec<_>) = self
-                .blobs
-                .iter()
-                .partition(|b| b.blob_type == BlobType::Prey);
+        let events_path = run_dir.file("events.jsonl");
+        let mut event_writer = if self.constants.log_events {
+            let file = open_output(&events_path, resume.map(|r| r.events))
+                .map_err(|e| Error::io(&events_path, e))?;
+            Some(EventWriter::from_file(file))
+        } else {
+            None
+        };
 
-            let mean_energy = |v: &Vec<&Blob>| {
-                if v.is_empty() {
-                    0.0
-                } else {
-                    v.iter().map(|b| b.energy).sum::<f32>() / v.len() as f32
-                }
-            };
+        let hashes_path = run_dir.file(hashes::FILE);
+        let mut hash_writer = if self.constants.hash_state {
+            let file = open_output(&hashes_path, resume.map(|r| r.hashes))
+                .map_err(|e| Error::io(&hashes_path, e))?;
+            let mut writer = BufWriter::new(file);
+            if resume.is_none() {
+                writeln!(writer, "{}", hashes::HEADER).map_err(|e| Error::io(&hashes_path, e))?;
+            }
+            Some(writer)
+        } else {
+            None
+        };
 
-            writeln!(
-                log,
-                "{},{},{},{},{}",
-                age,
-                preys.len(),
-                preds.len(),
-                mean_energy(&preys),
-                mean_energy(&preds)
-            )
-            .unwrap();
+        // live frames, numbered by age over render_every
+        let animation_dir = run_dir.file("animation");
+        if self.constants.render_every > 0 {
+            fs::create_dir_all(&animation_dir).map_err(|e| Error::io(&animation_dir, e))?;
+        }
+
+        let mut last_age = (start_age - 1).max(0);
+        let mut reason = ExitReason::Completed;
+        for age in start_age..self.constants.ages {
+            let report = self.update(age, rng, controller.as_deref_mut())?;
+            last_age = age;
+            let every = self.constants.render_every;
+            if every > 0 && age % every == 0 {
+                let filename = format!("{animation_dir}/frame{:04}.png", age / every);
+                println!("{filename}");
+                self.graph(&filename, self.constants.graph_neurons)
+                    .map_err(|e| Error::Render {
+                        path: filename,
+                        message: e.to_string(),
+                    })?;
+            }
+            if let Some(writer) = &mut event_writer {
+                writer
+                    .write_events(&report.events)
+                    .map_err(|e| Error::io(&events_path, e))?;
+            }
+            let stats = self.stats(age, &report);
+            writeln!(log, "{}", stats.csv_row(&self.constants.log_columns)?)
+                .map_err(|e| Error::io(&log_path, e))?;
 
             if let Some(writer) = &mut frame_writer {
                 writer
-                    .write_frame(age as usize, &self.blobs)
-                    .expect("Couldnt write current frame");
+                    .write_frame(age as usize, &self.blobs, &self.pheromones)
+                    .map_err(|e| Error::io(&frames_path, e))?;
+            }
+            if let Some(writer) = &mut hash_writer {
+                writeln!(writer, "{}", hashes::row(age, hashes::state_hash(self)))
+                    .map_err(|e| Error::io(&hashes_path, e))?;
             }
 
             let text = format!(
                 "age: {}, all: {}, prey: {}, predators: {}",
                 age,
-                blobs_count,
-                preys.len(),
-                preds.len()
+                self.blobs.len(),
+                stats.prey.count,
+                stats.predators.count
             );
 
             println!("{text}");
 
-            if preys.is_empty() || preds.is_empty() {
+            if stats.prey.count == 0 || stats.predators.count == 0 {
                 println!("someone got extinct");
+                reason = ExitReason::Extinct;
                 break;
             }
+
+            let every = self.constants.checkpoint_every;
+            if every > 0 && (age + 1) % every == 0 {
+                let outputs = OutputLengths {
+                    log: log.stream_position().map_err(|e| Error::io(&log_path, e))?,
+                    frames: match &mut frame_writer {
+                        Some(writer) => {
+                            writer.position().map_err(|e| Error::io(&frames_path, e))?
+                        }
+                        None => 0,
+                    },
+                    events: match &mut event_writer {
+                        Some(writer) => {
+                            writer.position().map_err(|e| Error::io(&events_path, e))?
+                        }
+                        None => 0,
+                    },
+                    hashes: match &mut hash_writer {
+                        Some(writer) => writer
+                            .stream_position()
+                            .map_err(|e| Error::io(&hashes_path, e))?,
+                        None => 0,
+                    },
+                    // steered by brains, a log from before stays as long as it was
+                    agent: match &mut controller {
+                        Some(controller) => controller.log_len().map_err(Error::Agent)?,
+                        None => None,
+                    }
+                    .or(resume.map(|r| r.agent))
+                    .unwrap_or(0),
+                };
+                let checkpoint = Checkpoint {
+                    world: self.clone(),
+                    rng: rng.clone(),
+                    age: age + 1,
+                    outputs,
+                };
+                checkpoint.save(&run_dir.checkpoints(), self.constants.checkpoints_kept)?;
+            }
+        }
+        log.flush().map_err(|e| Error::io(&log_path, e))?;
+        if let Some(writer) = &mut hash_writer {
+            writer.flush().map_err(|e| Error::io(&hashes_path, e))?;
+        }
+        if let Some(writer) = &mut frame_writer {
+            writer.flush().map_err(|e| Error::io(&frames_path, e))?;
         }
+        if let Some(writer) = &mut event_writer {
+            writer.flush().map_err(|e| Error::io(&events_path, e))?;
+        }
+
+        if self.constants.phylogeny {
+            let prune = self.constants.prune_phylogeny;
+            let csv_path = run_dir.file("phylogeny.csv");
+            self.phylogeny
+                .write_csv(&csv_path, prune)
+                .map_err(|e| Error::io(&csv_path, e))?;
+            let newick_path = run_dir.file("phylogeny.nwk");
+            self.phylogeny
+                .write_newick(&newick_path, last_age, prune)
+                .map_err(|e| Error::io(&newick_path, e))?;
+        }
+        Ok(RunEnd { reason, last_age })
     }
 
-    pub fn save_to_file(&self, filename: &str) -> io::Result<()> {
-        let encoded: Vec<u8> = bincode::serialize(self).expect("Failed to serialize");
-        let mut file = File::create(filename)?;
-        file.write_all(&encoded)?;
-        Ok(())
+    pub fn stats(&self, age: i32, report: &UpdateReport) -> Stats {
+        Stats::collect(self, age, &report.demography)
     }
 
-    pub fn if_save(&self, output_filename: Option<String>) {
+    // versioned, see saves.rs
+    pub fn save_to_file(&self, filename: &str) -> Result<()> {
+        let encoded = saves::encode(self).map_err(|e| Error::io(filename, e))?;
+        let mut file = File::create(filename).map_err(|e| Error::io(filename, e))?;
+        file.write_all(&encoded).map_err(|e| Error::io(filename, e))
+    }
+
+    pub fn if_save(&self, output_filename: Option<String>) -> Result<()> {
         if let Some(filename) = output_filename {
             println!("saving world");
-            self.save_to_file(&filename)
-                .expect("something wong saving world");
+            self.save_to_file(&filename)?;
         }
+        Ok(())
     }
 
-    pub fn load_from_file(filename: &str) -> io::Result<World> {
-        let mut file = File::open(filename)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-        let world: World = bincode::deserialize(&buffer).expect("Failed to deserialize");
-        Ok(world)
+    pub fn load_from_file(filename: &str) -> Result<World> {
+        let read = || -> io::Result<World> {
+            let mut buffer = Vec::new();
+            File::open(filename)?.read_to_end(&mut buffer)?;
+            saves::decode(&buffer)
+        };
+        read().map_err(|e| Error::io(filename, e))
     }
 
-    pub fn load_or_start(input_filename: Option<String>, rng: &mut impl Rng) -> Self {
+    // a saved world's constants are settled by the policy, `constants` are the ones a fresh world gets
+    pub fn load_or_start(
+        input_filename: Option<String>,
+        constants: Constants,
+        policy: &LoadPolicy,
+        rng: &mut impl Rng,
+    ) -> Result<Self> {
         if let Some(filename) = input_filename {
             println!("loading world");
-            let world = World::load_from_file(&filename).expect("something wong loading world");
-            world
+            let mut world = World::load_from_file(&filename)?;
+            world.constants = policy.apply(world.constants, constants)?;
+            Ok(world)
         } else {
             println!("generating world");
-            let constants = load_constants();
-            let network_shape = vec![constants.input_neurons_num, 2];
-            let world = Self::random_init(
-                constants.num_prey,
-                constants.num_predators,
-                network_shape,
-                constants,
-                rng,
-            );
-            world
+            Ok(Self::generate(constants, rng))
         }
     }
 
+    // a fresh random population sized and shaped by the constants
+    pub fn generate(constants: Constants, rng: &mut impl Rng) -> World {
+        let network_shape = constants.network_shape();
+        Self::random_init(
+            constants.num_prey,
+            constants.num_predators,
+            network_shape,
+            constants,
+            rng,
+        )
+    }
+
     pub fn random_init(
         num_prey: i32,
         num_predators: i32,
@@ -410,7 +811,9 @@ impl World {
         rng: &mut impl Rng,
     ) -> World {
         let mut blobs = Vec::new();
-        for _ in 0..num_prey {
+        let mut agent_slots = 0..;
+        let plastic = !matches!(which_plasticity(&constants), Plasticity::None);
+        for prey_idx in 0..num_prey {
             let position = (
                 rng.gen_range(0.0..constants.world_shape.0),
                 rng.gen_range(0.0..constants.world_shape.1),
@@ -420,17 +823,28 @@ impl World {
             let separation = 0.1; // rng.gen_range(0.0..std::f32::consts::TAU / network_shape[0] as f32);
 
             let activation = which_activation(&constants);
+            let genome = Genome::random(
+                &network_shape,
+                separation,
+                constants.mutation_rate,
+                Body::from_constants(&constants),
+                plastic,
+                rng,
+            );
             let brain = Brain::new(
+                &genome,
                 network_shape.clone(),
-                separation,
+                constants.input_neurons_num,
                 constants.neuron_length,
-                None,
                 activation,
-                rng,
             );
-            blobs.push(Blob::new(brain, position, angle, BlobType::Prey, 1.0));
+            let mut blob = Blob::new(genome, brain, position, angle, BlobType::Prey, 1.0);
+            if prey_idx < constants.controlled_prey {
+                blob.agent = agent_slots.next();
+            }
+            blobs.push(blob);
         }
-        for _ in 0..num_predators {
+        for predator_idx in 0..num_predators {
             let position = (
                 rng.gen_range(0.0..constants.world_shape.0),
                 rng.gen_range(0.0..constants.world_shape.1),
@@ -439,17 +853,138 @@ impl World {
             let angle = rng.gen_range(0.0..std::f32::consts::TAU);
 
             let activation = which_activation(&constants);
+            let genome = Genome::random(
+                &network_shape,
+                0.1,
+                constants.mutation_rate,
+                Body::from_constants(&constants),
+                plastic,
+                rng,
+            );
             let brain = Brain::new(
+                &genome,
                 network_shape.clone(),
-                0.1,
+                constants.input_neurons_num,
                 constants.neuron_length,
-                None,
                 activation,
-                rng,
             );
-            blobs.push(Blob::new(brain, position, angle, BlobType::Predator, 1.0));
+            let mut blob = Blob::new(genome, brain, position, angle, BlobType::Predator, 1.0);
+            if predator_idx < constants.controlled_predators {
+                blob.agent = agent_slots.next();
+            }
+            blobs.push(blob);
         }
 
         World::new(blobs, constants)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mods::constants::small_constants;
+    use rand::SeedableRng;
+    use std::fs;
+
+    // the small test world, checkpointing often and writing every output
+    fn recorded_constants() -> Constants {
+        Constants {
+            ages: 9,
+            dump_frames: true,
+            log_events: true,
+            hash_state: true,
+            phylogeny: true,
+            checkpoint_every: 3,
+            checkpoints_kept: 10,
+            ..small_constants()
+        }
+    }
+
+    fn outputs(run_dir: &RunDir) -> Vec<(&'static str, Vec<u8>)> {
+        [
+            "stats.csv",
+            "frames.bin",
+            "events.jsonl",
+            "hashes.csv",
+            "phylogeny.csv",
+        ]
+        .into_iter()
+        .map(|name| (name, fs::read(run_dir.file(name)).unwrap()))
+        .collect()
+    }
+
+    // one predator on top of three prey, after the kills of one age
+    fn feeding(handling_time: i32) -> World {
+        let constants = Constants {
+            handling_time,
+            ..small_constants()
+        };
+        let mut rng = WorldRng::seed_from_u64(1);
+        let network_shape = constants.network_shape();
+        let mut world = World::random_init(3, 1, network_shape, constants, &mut rng);
+        for blob in &mut world.blobs {
+            blob.position = (30.0, 20.0);
+        }
+        let of_type = |blob_type| {
+            (0..world.blobs.len())
+                .filter(|&i| world.blobs[i].blob_type == blob_type)
+                .collect::<Vec<_>>()
+        };
+        let (prey_indexes, predator_indexes) =
+            (of_type(BlobType::Prey), of_type(BlobType::Predator));
+        let interactions = world.check_interactions(&predator_indexes, &prey_indexes);
+        let mut report = UpdateReport::default();
+        world.kills(interactions, &prey_indexes, 0, &mut rng, &mut report);
+        world
+    }
+
+    fn predator(world: &World) -> &Blob {
+        world
+            .blobs
+            .iter()
+            .find(|blob| blob.blob_type == BlobType::Predator)
+            .unwrap()
+    }
+
+    #[test]
+    fn without_handling_a_predator_eats_all_it_reaches() {
+        let world = feeding(0);
+        assert_eq!(world.blobs.len(), 1);
+        assert_eq!(predator(&world).handling, 0);
+    }
+
+    #[test]
+    fn handling_blocks_the_next_handling_time_ages() {
+        let mut world = feeding(2);
+        assert_eq!(world.blobs.len(), 3, "one kill, then the predator is busy");
+        for _ in 0..2 {
+            world.digest();
+            assert!(!predator(&world).can_attack(0.0));
+        }
+        world.digest();
+        assert!(predator(&world).can_attack(0.0));
+    }
+
+    #[test]
+    fn resumed_run_writes_the_same_outputs() {
+        let root = std::env::temp_dir().join(format!("blobworld-resume-{}", std::process::id()));
+        let root = root.to_string_lossy().to_string();
+        let constants = recorded_constants();
+        let mut rng = WorldRng::seed_from_u64(constants.seed as u64);
+        let mut world = World::generate(constants.clone(), &mut rng);
+        let run_dir = RunDir::create(&root, Some("run"), constants.seed, None).unwrap();
+        world.evolve(&run_dir, &mut rng, None).unwrap();
+        let uninterrupted = outputs(&run_dir);
+
+        // going on from the first checkpoint rewrites everything after it
+        let checkpoint =
+            Checkpoint::load(&format!("{}/000003.ckpt", run_dir.checkpoints())).unwrap();
+        World::resume(&run_dir, checkpoint, None).unwrap();
+        let resumed = outputs(&run_dir);
+        fs::remove_dir_all(&root).unwrap();
+
+        for ((name, before), (_, after)) in uninterrupted.iter().zip(&resumed) {
+            assert!(before == after, "{name} differs after resuming");
+        }
+    }
+}